@@ -0,0 +1,16 @@
+//! Writes a TypeScript client for `DemoRouter` to the given file, or to stdout.
+//!
+//! `cargo run --bin typescript -- web/api.ts`
+
+use {server::DemoRouter, std::env};
+
+fn main() -> std::io::Result<()> {
+    let doc = DemoRouter::openapi();
+    match env::args().nth(1) {
+        Some(path) => milrouter::typescript::write(path, &doc),
+        None => {
+            print!("{}", milrouter::typescript::generate(&doc));
+            Ok(())
+        }
+    }
+}
@@ -11,18 +11,47 @@ use hyper::{Request, Response, body::Incoming};
 use http_body_util::Full;
 use milrouter::futures::future::BoxFuture;
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
 pub struct SearchQuery {
     pub needle: String,
     pub haystack: Vec<String>,
 }
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
 pub struct SearchResult {
     pub matches: Vec<String>,
     pub total: usize,
 }
 
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct ClientFilter {
+    pub prefix: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct Greeting {
+    pub name: String,
+    #[serde(default)]
+    pub shout: bool,
+}
+
+/// Endpoints can return their own error type instead of `anyhow::Error`.
+/// Each variant picks its status code, and the typed client gets the variant back.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub enum DefineError {
+    Unknown { word: String },
+    Forbidden,
+}
+
+impl milrouter::ApiError for DefineError {
+    fn status(&self) -> u16 {
+        match self {
+            DefineError::Unknown { .. } => 404,
+            DefineError::Forbidden => 403,
+        }
+    }
+}
+
 fn super_awesome_html_generator() -> String {
     "<!doctype html><html><head><meta charset=\"utf-8\"><title>milrouter demo</title></head><body><h1>milrouter demo</h1></body></html>".to_string()
 }
@@ -55,21 +84,23 @@ impl RateLimiter {
 }
 
 /// Stateful middleware that limits requests per client IP.
+/// One instance serves every request, so the counters live behind a `Mutex`.
 struct RateLimitMiddleware {
-    limiter: RateLimiter,
+    limiter: std::sync::Mutex<RateLimiter>,
 }
 
 impl RateLimitMiddleware {
     fn new() -> Self {
-        Self { limiter: RateLimiter::new(10, Duration::from_secs(60)) }
+        Self { limiter: std::sync::Mutex::new(RateLimiter::new(10, Duration::from_secs(60))) }
     }
 }
 
 impl milrouter::Middleware for RateLimitMiddleware {
     /// Short-circuit with 429 when the client exceeds the rate limit.
     fn before(
-        &mut self,
+        &self,
         req: &Request<Incoming>,
+        _cx: &mut hyper::http::Extensions,
     ) -> BoxFuture<'static, anyhow::Result<Option<Response<Full<Bytes>>>>> {
         let client_ip = req.headers()
             .get("x-forwarded-for")
@@ -81,7 +112,7 @@ impl milrouter::Middleware for RateLimitMiddleware {
             .trim()
             .to_string();
 
-        let allowed = self.limiter.check(&client_ip);
+        let allowed = self.limiter.lock().unwrap().check(&client_ip);
 
         Box::pin(async move {
             if !allowed {
@@ -96,33 +127,124 @@ impl milrouter::Middleware for RateLimitMiddleware {
             Ok(None)
         })
     }
+
+    /// Tell clients what they're allowed.
+    fn after(
+        &self,
+        res: &mut Response<()>,
+        _cx: &mut hyper::http::Extensions,
+    ) -> BoxFuture<'static, anyhow::Result<()>> {
+        let limit = self.limiter.lock().unwrap().limit;
+        res.headers_mut().insert("X-RateLimit-Limit", hyper::header::HeaderValue::from(limit));
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// Per-endpoint middleware: marks responses as cacheable.
+struct CacheControlMiddleware;
+
+impl CacheControlMiddleware {
+    fn new() -> Self { CacheControlMiddleware }
+}
+
+impl milrouter::Middleware for CacheControlMiddleware {
+    fn after(
+        &self,
+        res: &mut Response<()>,
+        _cx: &mut hyper::http::Extensions,
+    ) -> BoxFuture<'static, anyhow::Result<()>> {
+        res.headers_mut().insert("Cache-Control", hyper::header::HeaderValue::from_static("public, max-age=3600"));
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// Response signing: buffers the body to put its (FNV-1a) digest in an `X-Checksum` header.
+struct ChecksumMiddleware;
+
+impl ChecksumMiddleware {
+    fn new() -> Self { ChecksumMiddleware }
+}
+
+impl milrouter::Middleware for ChecksumMiddleware {
+    fn map_response(
+        &self,
+        res: Response<milrouter::MilBody>,
+        _cx: &mut hyper::http::Extensions,
+    ) -> BoxFuture<'static, anyhow::Result<Response<milrouter::MilBody>>> {
+        use http_body_util::BodyExt;
+
+        Box::pin(async move {
+            let (mut parts, body) = res.into_parts();
+            let bytes = body.collect().await?.to_bytes();
+            let digest = bytes.iter().fold(0xcbf29ce484222325u64, |h, b| (h ^ *b as u64).wrapping_mul(0x100000001b3));
+            parts.headers.insert("X-Checksum", hyper::header::HeaderValue::from_str(&format!("{digest:016x}"))?);
+            Ok(Response::from_parts(parts, Full::new(bytes).boxed()))
+        })
+    }
+}
+
+/// Numbers each request.  Set by `RequestIdMiddleware` and read by endpoints as `Extension<RequestId>`.
+#[derive(Debug, Clone, Copy)]
+pub struct RequestId(pub u64);
+
+/// Assigns every request an ID, hands it to the endpoint, and echoes it as `X-Request-Id`.
+struct RequestIdMiddleware {
+    next: std::sync::atomic::AtomicU64,
+}
+
+impl RequestIdMiddleware {
+    fn new() -> Self { Self { next: std::sync::atomic::AtomicU64::new(1) } }
+}
+
+impl milrouter::Middleware for RequestIdMiddleware {
+    fn before(
+        &self,
+        _req: &Request<Incoming>,
+        cx: &mut hyper::http::Extensions,
+    ) -> BoxFuture<'static, anyhow::Result<Option<Response<Full<Bytes>>>>> {
+        cx.insert(RequestId(self.next.fetch_add(1, std::sync::atomic::Ordering::SeqCst)));
+        Box::pin(async { Ok(None) })
+    }
+
+    fn after(
+        &self,
+        res: &mut Response<()>,
+        cx: &mut hyper::http::Extensions,
+    ) -> BoxFuture<'static, anyhow::Result<()>> {
+        if let Some(RequestId(id)) = cx.get::<RequestId>() {
+            res.headers_mut().insert("X-Request-Id", hyper::header::HeaderValue::from(*id));
+        }
+        Box::pin(async { Ok(()) })
+    }
 }
 
+/// The request's `Origin`, stashed by `CorsMiddleware::before` for its `after` hook.
+#[derive(Clone)]
+struct CorsOrigin(String);
+
 /// Pipe-style middleware: lets requests through but injects CORS headers into
 /// every response via the `after` hook.  Also handles OPTIONS preflight in the
 /// `before` hook.
-struct CorsMiddleware {
-    origin: Option<String>,
-}
+struct CorsMiddleware;
 
 impl CorsMiddleware {
-    fn new() -> Self { CorsMiddleware { origin: None } }
+    fn new() -> Self { CorsMiddleware }
 }
 
 impl milrouter::Middleware for CorsMiddleware {
     /// Handle CORS preflight by short-circuiting with the appropriate headers.
-    /// Also stash the requested `Origin` so `after` can echo it back.
+    /// Also stash the requested `Origin` in the request's context so `after` can echo it back.
     fn before(
-        &mut self,
+        &self,
         req: &Request<Incoming>,
+        cx: &mut hyper::http::Extensions,
     ) -> BoxFuture<'static, anyhow::Result<Option<Response<Full<Bytes>>>>> {
-        self.origin = req.headers()
-            .get("origin")
-            .and_then(|v| v.to_str().ok())
-            .map(|s| s.to_string());
+        if let Some(origin) = req.headers().get("origin").and_then(|v| v.to_str().ok()) {
+            cx.insert(CorsOrigin(origin.to_string()));
+        }
 
         if req.method() == hyper::Method::OPTIONS {
-            let origin = self.origin.as_deref().unwrap_or("*").to_string();
+            let origin = cx.get::<CorsOrigin>().map_or("*", |o| o.0.as_str()).to_string();
             Box::pin(async move {
                 let res = Response::builder()
                     .status(200)
@@ -141,13 +263,14 @@ impl milrouter::Middleware for CorsMiddleware {
 
     /// Add CORS headers to every response that makes it past `before`.
     fn after(
-        &mut self,
+        &self,
         res: &mut Response<()>,
+        cx: &mut hyper::http::Extensions,
     ) -> BoxFuture<'static, anyhow::Result<()>> {
-        let origin = self.origin.as_deref().unwrap_or("*").to_string();
+        let origin = cx.get::<CorsOrigin>().map_or("*", |o| o.0.as_str());
         res.headers_mut().insert(
             "Access-Control-Allow-Origin",
-            hyper::header::HeaderValue::from_str(&origin).unwrap_or_else(|_| hyper::header::HeaderValue::from_static("*")),
+            hyper::header::HeaderValue::from_str(origin).unwrap_or_else(|_| hyper::header::HeaderValue::from_static("*")),
         );
         res.headers_mut().insert(
             "Access-Control-Allow-Methods",
@@ -199,24 +322,153 @@ fn search(headers: hyper::HeaderMap, query: SearchQuery) -> anyhow::Result<Searc
     Ok(SearchResult { total: matches.len(), matches })
 }
 
-#[endpoint(auth = auth_handler, raw)]
+/// Path parameters are matched by name against the function's arguments,
+/// and parsed with `FromStr`.
+#[endpoint(auth = auth_handler, idempotent = true, path = "/repeat/{word}/{times}")]
+fn repeat(word: String, times: usize) -> anyhow::Result<String> { Ok(word.repeat(times)) }
+
+/// `GET` endpoints read their input from the query string, e.g. `/greet?name=milrouter&shout=true`,
+/// so responses can be cached like any other page.
+#[endpoint(auth = milrouter::all_aboard, method = get)]
+fn greet(greeting: Greeting) -> anyhow::Result<String> {
+    let text = format!("Hello, {}!", greeting.name);
+    Ok(if greeting.shout { text.to_uppercase() } else { text })
+}
+
+#[endpoint(auth = milrouter::all_aboard, method = get, path = "/define/{word}")]
+fn define(word: String) -> Result<String, DefineError> {
+    match word.as_str() {
+        "router" => Ok("Something that decides where requests go.".to_string()),
+        "admin" => Err(DefineError::Forbidden),
+        _ => Err(DefineError::Unknown { word }),
+    }
+}
+
+/// Argument roles can be spelled out with `#[auth]`, `#[headers]` and `#[body]`.
+/// Without `#[body]`, `client_filter` would be taken for the auth client because of its name.
+#[endpoint(auth = auth_handler)]
+fn clients(#[auth] _caller: (), #[headers] _request_headers: hyper::HeaderMap, #[body] client_filter: ClientFilter) -> anyhow::Result<Vec<String>> {
+    Ok(["alpha", "alpine", "beta"].into_iter().filter(|c| c.starts_with(&client_filter.prefix)).map(String::from).collect())
+}
+
+/// Several body arguments are sent together as one JSON object, `{"a": 1, "b": 2}`,
+/// and the typed client takes them one by one: `client.add(1, 2)`.
+#[endpoint(auth = milrouter::all_aboard)]
+fn add(a: i64, b: i64) -> anyhow::Result<i64> { Ok(a + b) }
+
+/// Shared by every request once the router is served with `DemoRouter::with_state(AppState::default())`.
+#[derive(Default)]
+pub struct AppState {
+    pub visits: std::sync::atomic::AtomicUsize,
+}
+
+/// Endpoints borrow the router's state with a `&AppState` argument (or take a `milrouter::State<AppState>`).
+#[endpoint(auth = milrouter::all_aboard, idempotent = true)]
+fn visit(state: &AppState) -> anyhow::Result<usize> {
+    Ok(state.visits.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1)
+}
+
+/// Values middleware inserts into the request's context arrive as `Extension<T>` arguments.
+#[endpoint(auth = milrouter::all_aboard, idempotent = true, middleware(RequestIdMiddleware))]
+fn request_id(id: milrouter::Extension<RequestId>) -> anyhow::Result<u64> { Ok(id.0.0) }
+
+/// Marks every response from `AdminRouter`, on top of whatever `DemoRouter`'s middleware does.
+struct AuditMiddleware;
+
+impl AuditMiddleware {
+    fn new() -> Self { AuditMiddleware }
+}
+
+impl milrouter::Middleware for AuditMiddleware {
+    fn after(
+        &self,
+        res: &mut Response<()>,
+        _cx: &mut hyper::http::Extensions,
+    ) -> BoxFuture<'static, anyhow::Result<()>> {
+        res.headers_mut().insert("X-Audited", hyper::header::HeaderValue::from_static("admin"));
+        Box::pin(async { Ok(()) })
+    }
+}
+
+pub async fn admin_auth(headers: hyper::HeaderMap) -> anyhow::Result<()> {
+    match headers.get("authorization").is_some_and(|v| v == "Bearer admin") {
+        true => Ok(()),
+        false => Err(anyhow::anyhow!("Admins only.")),
+    }
+}
+
+#[endpoint(auth = admin_auth)]
+fn ban_user(user: String) -> anyhow::Result<String> { Ok(format!("{user} has been banned")) }
+
+#[endpoint(auth = admin_auth, method = get, path = "/users/{id}")]
+fn admin_user(id: u64) -> anyhow::Result<String> { Ok(format!("user #{id}")) }
+
+/// Nested in `DemoRouter` under `/admin`: `/admin/ban_user`, `/admin/users/{id}`.
+#[derive(Router)]
+#[middleware(AuditMiddleware)]
+pub enum AdminRouter {
+    BanUser(EndpointBanUser),
+    AdminUser(EndpointAdminUser),
+}
+
+/// `middleware(...)` runs only for this endpoint, after the router's and its groups' middleware.
+#[endpoint(auth = auth_handler, raw, middleware(CacheControlMiddleware, ChecksumMiddleware))]
 fn version_blob() -> anyhow::Result<Vec<u8>> { Ok(b"milrouter-demo-v2\n".to_vec()) }
 
+/// Answers requests no route matched.  The old `/time` path redirects, browsers navigating to an
+/// unknown page get the HTML shell so client-side routing can take over, and API clients a JSON 404.
+/// Under a `Mount` the path arrives without the prefix, so the redirect puts it back.
+async fn not_found(req: Request<Incoming>) -> anyhow::Result<Response<milrouter::MilBody>> {
+    use http_body_util::BodyExt;
+
+    let path = req.uri().path();
+    if path == "/time" {
+        let prefix = req.extensions().get::<milrouter::MountPrefix>().map(|prefix| prefix.0.as_str()).unwrap_or_default();
+        let location = format!("{prefix}/the_time");
+        return Ok(Response::builder().status(308).header("location", location).body(Full::default().boxed())?);
+    }
+
+    let wants_html = req.method() == hyper::Method::GET
+        && req.headers().get("accept").and_then(|a| a.to_str().ok()).is_some_and(|a| a.contains("text/html"));
+    if wants_html {
+        let shell = Full::new(Bytes::from(super_awesome_html_generator()));
+        return Ok(Response::builder().status(200).header("content-type", "text/html").body(shell.boxed())?);
+    }
+
+    let body = milrouter::serde_json::json!({ "error": "not found", "path": path });
+    let body = Full::new(Bytes::from(milrouter::serde_json::to_vec(&body)?));
+    Ok(Response::builder().status(404).header("content-type", "application/json").body(body.boxed())?)
+}
+
 #[derive(Router)]
 #[assets("./example/static")] // Optional.
                                // Serves static assets (relative to the file in which its invoked)
                                // If `MILROUTER_LOCAL` is set, will read from disk every request
                                // Otherwise, will load into LazyLock
 #[html(super_awesome_html_generator)] // Optional.
-#[middleware(RateLimitMiddleware, CorsMiddleware)]
+#[fallback(not_found)] // Optional. Answers requests no route matched, instead of an empty 404.
+#[middleware(CorsMiddleware)] // Optional. Runs for every request, assets included.
+#[group(expensive(RateLimitMiddleware))] // Optional. Named middleware, run for the variants that join it.
+#[openapi("/openapi.json")] // Optional, needs milrouter's `openapi` feature.
+                            // Serves `DemoRouter::openapi()` at this path.
 pub enum DemoRouter {
     TheTime(EndpointTheTime), // `EndpointTheTime` is created by the #[endpoint] macro.
                               // It impls the traits required to make requests.
                               //
                               // `TheTime` can be named however youd like. It corresponds
                               //  to the underlying route name.
+    #[group(expensive)]
     Search(EndpointSearch),
     VersionBlob(EndpointVersionBlob),
+    Repeat(EndpointRepeat), // Served at `/repeat/{word}/{times}` rather than `/repeat`.
+    Greet(EndpointGreet),
+    Define(EndpointDefine),
+    Visit(EndpointVisit), // Needs `DemoRouter::with_state(AppState)`.
+    Clients(EndpointClients),
+    Add(EndpointAdd),
+    RequestId(EndpointRequestId),
+    #[nest]
+    Admin(AdminRouter), // Another router, served under `/admin` with its middleware inside ours.
 }
 
 #[cfg(not(any(target_arch = "wasm32", target_arch = "wasm64")))]
@@ -226,14 +478,14 @@ pub async fn query_tools_demo(base_url: &str) -> anyhow::Result<(String, SearchR
 
     let client = DemoRouter::client(base_url.to_string(), headers);
 
-    let now = client.the_time(()).await?;
+    let now = client.the_time().await?;
     let search = client
         .search(SearchQuery {
             needle: "or".to_string(),
             haystack: vec!["router".to_string(), "planet".to_string(), "orbit".to_string()],
         })
         .await?;
-    let blob = client.version_blob(()).await?;
+    let blob = client.version_blob().await?;
 
     Ok((now, search, blob))
 }
\ No newline at end of file
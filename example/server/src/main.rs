@@ -1,6 +1,6 @@
 use {
     milrouter::tracing::{Level, info},
-    server::DemoRouter,
+    server::{AppState, DemoRouter},
     std::env,
 };
 
@@ -13,5 +13,5 @@ fn main() {
             .unwrap();
 
     info!("Starting milrouter demo on http://{addr}");
-    milrouter::serve_local(addr, DemoRouter::new()).unwrap();
+    milrouter::serve_local(addr, DemoRouter::with_state(AppState::default())).unwrap();
 }
@@ -0,0 +1,20 @@
+mod common;
+
+use {
+    milrouter::{Router, testing::TestServer},
+    server::{ClientFilter, DemoRouter},
+};
+
+#[test]
+fn marked_body_is_not_mistaken_for_the_auth_client() {
+    let client = DemoRouter::client_with_transport("http://test".into(), Default::default(), TestServer::new(DemoRouter::new()));
+
+    let found = common::runtime().block_on(client.clients(ClientFilter { prefix: "al".into() })).unwrap();
+    assert_eq!(found, vec!["alpha", "alpine"]);
+}
+
+#[test]
+fn marked_body_is_the_endpoint_input() {
+    let clients = DemoRouter::new().routes().into_iter().find(|r| r.path == "/clients").unwrap();
+    assert_eq!(clients.input, "ClientFilter");
+}
@@ -0,0 +1,60 @@
+mod common;
+
+use {
+    milrouter::{
+        InputError,
+        Router,
+        bytes::Bytes,
+        hyper::{Request, StatusCode},
+        testing::TestServer,
+    },
+    server::{DemoRouter, EndpointAdd, EndpointRepeat, EndpointTheTime},
+};
+
+#[test]
+fn client_takes_body_arguments_individually() {
+    let client = DemoRouter::client_with_transport("http://test".into(), Default::default(), TestServer::new(DemoRouter::new()));
+
+    assert_eq!(common::runtime().block_on(client.add(2, 40)).unwrap(), 42);
+}
+
+type Sum = EndpointAdd;
+
+// Endpoints declared in another crate, one of them behind a type alias.
+#[derive(Router)]
+enum ForeignRouter {
+    TheTime(EndpointTheTime),
+    Add(Sum),
+    Repeat(EndpointRepeat),
+}
+
+#[test]
+fn routers_take_endpoints_from_other_crates() {
+    let client = ForeignRouter::client_with_transport("http://test".into(), Default::default(), TestServer::new(ForeignRouter::new()));
+
+    common::runtime().block_on(async {
+        assert!(client.the_time().await.is_ok());
+        assert_eq!(client.add(2, 40).await.unwrap(), 42);
+        assert_eq!(client.repeat("ab".to_string(), 3).await.unwrap(), "ababab");
+    });
+}
+
+#[test]
+fn body_arguments_are_fields_of_one_object() {
+    let server = TestServer::new(DemoRouter::new());
+
+    let req = Request::post("/add").body(Bytes::from(r#"{"a": 7, "b": -2}"#)).unwrap();
+    let res = common::runtime().block_on(server.request(req)).unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.json::<i64>().unwrap(), 5);
+}
+
+#[test]
+fn missing_body_argument_is_a_bad_request() {
+    let server = TestServer::new(DemoRouter::new());
+
+    let req = Request::post("/add").body(Bytes::from(r#"{"a": 7}"#)).unwrap();
+    let res = common::runtime().block_on(server.request(req)).unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    assert!(res.json::<InputError>().unwrap().message.contains("missing field `b`"));
+}
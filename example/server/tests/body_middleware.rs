@@ -0,0 +1,84 @@
+mod common;
+
+use {
+    milrouter::{
+        BoxFuture,
+        Middleware,
+        MilBody,
+        anyhow,
+        bytes::Bytes,
+        futures::stream,
+        http_body_util::BodyExt,
+        hyper::{Request, Response, StatusCode, http::Extensions},
+        testing::TestServer,
+    },
+    server::DemoRouter,
+    std::sync::Arc,
+};
+
+/// Uppercases each chunk as it passes, without waiting for the rest of the body.
+struct Shout;
+
+impl Middleware for Shout {
+    fn map_response(
+        &self,
+        res: Response<MilBody>,
+        _cx: &mut Extensions,
+    ) -> BoxFuture<'static, anyhow::Result<Response<MilBody>>> {
+        let res = res.map(|body| body.map_frame(|frame| frame.map_data(|data| Bytes::from(data.to_ascii_uppercase()))).boxed());
+        Box::pin(async { Ok(res) })
+    }
+}
+
+struct Broken;
+
+impl Middleware for Broken {
+    fn map_response(
+        &self,
+        _res: Response<MilBody>,
+        _cx: &mut Extensions,
+    ) -> BoxFuture<'static, anyhow::Result<Response<MilBody>>> {
+        Box::pin(async { Err(anyhow::anyhow!("signing key unavailable")) })
+    }
+}
+
+#[test]
+fn map_response_can_replace_the_body() {
+    let server = TestServer::new(DemoRouter::new());
+    let res = common::runtime().block_on(server.request(Request::post("/version_blob").body(Bytes::new()).unwrap())).unwrap();
+
+    assert_eq!(res.status(), StatusCode::OK);
+    let body = res.bytes().unwrap();
+    let digest = body.iter().fold(0xcbf29ce484222325u64, |h, b| (h ^ *b as u64).wrapping_mul(0x100000001b3));
+    assert_eq!(res.headers()["x-checksum"], format!("{digest:016x}"));
+    // Header-only `after` hooks still apply alongside it.
+    assert_eq!(res.headers()["cache-control"], "public, max-age=3600");
+}
+
+#[test]
+fn map_response_wraps_streaming_bodies_chunk_by_chunk() {
+    common::runtime().block_on(async {
+        let chunks = stream::iter(["one ", "two ", "three"].map(Bytes::from));
+        let res = Response::new(milrouter::stream_to_body(milrouter::into_response_stream(chunks)));
+
+        let middleware: Vec<Arc<dyn Middleware>> = vec![Arc::new(Shout)];
+        let mut body = milrouter::run_after(&middleware, res, &mut Extensions::new()).await.into_body();
+
+        let mut seen = Vec::new();
+        while let Some(frame) = body.frame().await {
+            seen.push(frame.unwrap().into_data().unwrap());
+        }
+        assert_eq!(seen, ["ONE ", "TWO ", "THREE"].map(Bytes::from));
+    });
+}
+
+#[test]
+fn map_response_errors_answer_500() {
+    common::runtime().block_on(async {
+        let middleware: Vec<Arc<dyn Middleware>> = vec![Arc::new(Broken), Arc::new(Shout)];
+        let res = milrouter::run_after(&middleware, Response::new(MilBody::default()), &mut Extensions::new()).await;
+
+        assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(res.into_body().collect().await.unwrap().to_bytes(), "signing key unavailable");
+    });
+}
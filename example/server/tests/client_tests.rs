@@ -4,12 +4,12 @@ use {
 };
 
 fn spawn_server() -> (std::net::SocketAddr, tokio::runtime::Runtime) {
-    let probe = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
-    let addr = probe.local_addr().unwrap();
-    drop(probe);
+    // Binding up front means requests queue in the backlog until the server thread starts accepting.
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
 
     std::thread::spawn(move || {
-        let _ = milrouter::serve_local(addr, DemoRouter::new());
+        let _ = milrouter::serve_local_listener(listener, DemoRouter::new());
     });
 
     let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
@@ -17,21 +17,9 @@ fn spawn_server() -> (std::net::SocketAddr, tokio::runtime::Runtime) {
     (addr, rt)
 }
 
-fn wait_for_server(rt: &tokio::runtime::Runtime, addr: std::net::SocketAddr) {
-    let client = DemoRouter::client(format!("http://{addr}"), Default::default());
-    for _ in 0..40 {
-        if rt.block_on(client.the_time(())).is_ok() {
-            return;
-        }
-        std::thread::sleep(Duration::from_millis(25));
-    }
-    panic!("Server did not become ready in time");
-}
-
 #[test]
 fn client_returns_json_deserialized_response() {
     let (addr, rt) = spawn_server();
-    wait_for_server(&rt, addr);
 
     let client = DemoRouter::client(format!("http://{addr}"), Default::default());
 
@@ -49,18 +37,16 @@ fn client_returns_json_deserialized_response() {
 #[test]
 fn client_returns_raw_bytes_for_raw_endpoint() {
     let (addr, rt) = spawn_server();
-    wait_for_server(&rt, addr);
 
     let client = DemoRouter::client(format!("http://{addr}"), Default::default());
 
-    let raw = rt.block_on(client.version_blob(())).unwrap();
+    let raw = rt.block_on(client.version_blob()).unwrap();
     assert_eq!(raw, b"milrouter-demo-v2\n".to_vec());
 }
 
 #[test]
 fn client_preserves_custom_headers() {
     let (addr, rt) = spawn_server();
-    wait_for_server(&rt, addr);
 
     let mut headers = milrouter::hyper::HeaderMap::new();
     headers.insert("x-demo-client", milrouter::hyper::header::HeaderValue::from_static("test-header"));
@@ -76,14 +62,13 @@ fn client_preserves_custom_headers() {
 #[test]
 fn client_handles_401_unauthorized() {
     let (addr, rt) = spawn_server();
-    wait_for_server(&rt, addr);
 
     let mut headers = milrouter::hyper::HeaderMap::new();
     headers.insert("evil", milrouter::hyper::header::HeaderValue::from_static("true"));
 
     let client = DemoRouter::client(format!("http://{addr}"), headers);
 
-    let result = rt.block_on(client.the_time(()));
+    let result = rt.block_on(client.the_time());
 
     assert!(result.is_err());
     let err = result.unwrap_err().to_string();
@@ -97,7 +82,6 @@ fn client_handles_401_unauthorized() {
 #[test]
 fn client_uses_put_for_idempotent_endpoint() {
     let (addr, rt) = spawn_server();
-    wait_for_server(&rt, addr);
 
     let client = DemoRouter::client(format!("http://{addr}"), Default::default());
 
@@ -105,14 +89,13 @@ fn client_uses_put_for_idempotent_endpoint() {
     // The request itself should succeed; we can't easily observe the method
     // from the client side without intercepting, but we can at least verify
     // the endpoint is callable.
-    let result = rt.block_on(client.the_time(()));
+    let result = rt.block_on(client.the_time());
     assert!(result.is_ok(), "Idempotent endpoint request failed: {:?}", result);
 }
 
 #[test]
 fn client_uses_post_for_non_idempotent_endpoint() {
     let (addr, rt) = spawn_server();
-    wait_for_server(&rt, addr);
 
     let client = DemoRouter::client(format!("http://{addr}"), Default::default());
 
@@ -124,10 +107,34 @@ fn client_uses_post_for_non_idempotent_endpoint() {
 #[test]
 fn client_works_with_trailing_slash_in_host() {
     let (addr, rt) = spawn_server();
-    wait_for_server(&rt, addr);
 
     let client = DemoRouter::client(format!("http://{addr}/"), Default::default());
 
-    let result = rt.block_on(client.the_time(()));
+    let result = rt.block_on(client.the_time());
     assert!(result.is_ok(), "Client with trailing slash failed: {:?}", result);
 }
+
+#[test]
+fn server_stops_after_shutdown_signal() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+    let server = std::thread::spawn(move || {
+        milrouter::ServerBuilder::new(DemoRouter::new()).shutdown_grace(Duration::from_secs(1)).serve_local_listener_with_shutdown(
+            listener,
+            async move {
+                let _ = rx.await;
+            },
+        )
+    });
+
+    let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let client = DemoRouter::client(format!("http://{addr}"), Default::default());
+    assert!(rt.block_on(client.the_time()).is_ok(), "Server not answering before shutdown");
+
+    let _ = tx.send(());
+    server.join().expect("server thread panicked").expect("server returned an error while shutting down");
+
+    assert!(rt.block_on(client.the_time()).is_err(), "Server still answering after shutdown");
+}
@@ -0,0 +1,4 @@
+//! Helpers shared by the integration tests.
+
+/// A current-thread runtime for driving a test's async body with `block_on`.
+pub fn runtime() -> tokio::runtime::Runtime { tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap() }
@@ -0,0 +1,35 @@
+mod common;
+
+use {
+    milrouter::{
+        bytes::Bytes,
+        hyper::{Request, StatusCode},
+        testing::TestServer,
+    },
+    server::DemoRouter,
+};
+
+#[test]
+fn endpoint_receives_what_middleware_inserted() {
+    common::runtime().block_on(async {
+        let server = TestServer::new(DemoRouter::new());
+        let first = server.request(Request::put("/request_id").body(Bytes::new()).unwrap()).await.unwrap();
+        let second = server.request(Request::put("/request_id").body(Bytes::new()).unwrap()).await.unwrap();
+
+        assert_eq!(first.status(), StatusCode::OK);
+        let (first_id, second_id) = (first.json::<u64>().unwrap(), second.json::<u64>().unwrap());
+        assert_eq!(second_id, first_id + 1);
+
+        // `after` sees the same value the endpoint did.
+        assert_eq!(first.headers()["x-request-id"], first_id.to_string());
+        assert_eq!(second.headers()["x-request-id"], second_id.to_string());
+    });
+}
+
+#[test]
+fn typed_client_is_unaffected_by_extension_arguments() {
+    let server = TestServer::new(DemoRouter::new());
+    let client = DemoRouter::client_with_transport("http://test".into(), Default::default(), server);
+
+    assert!(common::runtime().block_on(client.request_id()).unwrap() >= 1);
+}
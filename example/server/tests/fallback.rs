@@ -0,0 +1,84 @@
+mod common;
+
+use {
+    milrouter::{
+        Mount,
+        bytes::Bytes,
+        hyper::{Request, StatusCode},
+        serde_json::{Value, json},
+        testing::TestServer,
+    },
+    server::DemoRouter,
+};
+
+#[test]
+fn unknown_api_paths_get_a_json_404() {
+    common::runtime().block_on(async {
+        let server = TestServer::new(DemoRouter::new());
+        let res = server.request(Request::put("/nope").body(Bytes::new()).unwrap()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        assert_eq!(res.headers()["content-type"], "application/json");
+        assert_eq!(res.json::<Value>().unwrap(), json!({ "error": "not found", "path": "/nope" }));
+
+        // A known path with the wrong method falls through too.
+        let res = server.request(Request::post("/greet").body(Bytes::from("{}")).unwrap()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        assert_eq!(res.json::<Value>().unwrap()["path"], "/greet");
+    });
+}
+
+#[test]
+fn browser_navigation_gets_the_html_shell() {
+    common::runtime().block_on(async {
+        let server = TestServer::new(DemoRouter::new());
+        let req = Request::get("/settings/profile").header("accept", "text/html,application/xhtml+xml").body(Bytes::new()).unwrap();
+        let res = server.request(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers()["content-type"], "text/html");
+        assert!(res.text().unwrap().contains("<h1>milrouter demo</h1>"));
+
+        // Without asking for HTML, the same path is an API miss.
+        let res = server.request(Request::get("/settings/profile").body(Bytes::new()).unwrap()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    });
+}
+
+#[test]
+fn fallback_can_redirect() {
+    common::runtime().block_on(async {
+        let server = TestServer::new(DemoRouter::new());
+        let res = server.request(Request::put("/time").body(Bytes::new()).unwrap()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(res.headers()["location"], "/the_time");
+    });
+}
+
+#[test]
+fn fallback_responses_pass_through_router_middleware() {
+    common::runtime().block_on(async {
+        let server = TestServer::new(DemoRouter::new());
+        let req = Request::put("/nope").header("origin", "https://a.example").body(Bytes::new()).unwrap();
+        let res = server.request(req).await.unwrap();
+        assert_eq!(res.headers()["access-control-allow-origin"], "https://a.example");
+    });
+}
+
+#[test]
+fn nested_routers_without_a_fallback_use_the_parents() {
+    common::runtime().block_on(async {
+        let server = TestServer::new(DemoRouter::new());
+        let res = server.request(Request::put("/admin/nope").body(Bytes::new()).unwrap()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        assert_eq!(res.json::<Value>().unwrap(), json!({ "error": "not found", "path": "/admin/nope" }));
+    });
+}
+
+#[test]
+fn mounted_redirects_keep_the_prefix() {
+    common::runtime().block_on(async {
+        let server = TestServer::new(Mount::new("/api/v1", DemoRouter::new()));
+        let res = server.request(Request::put("/api/v1/time").body(Bytes::new()).unwrap()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(res.headers()["location"], "/api/v1/the_time");
+    });
+}
@@ -0,0 +1,65 @@
+mod common;
+
+use {
+    milrouter::{
+        bytes::Bytes,
+        hyper::{Request, StatusCode},
+        testing::TestServer,
+    },
+    server::{DemoRouter, Greeting},
+};
+
+#[test]
+fn get_endpoint_reads_the_query_string() {
+    let server = TestServer::new(DemoRouter::new());
+
+    let res = common::runtime()
+        .block_on(server.request(Request::get("/greet?name=milrouter&shout=true").body(Bytes::new()).unwrap()))
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.json::<String>().unwrap(), "HELLO, MILROUTER!");
+}
+
+#[test]
+fn get_endpoint_rejects_other_methods() {
+    let server = TestServer::new(DemoRouter::new());
+
+    let res = common::runtime().block_on(server.request(Request::post("/greet").body(Bytes::from("{}")).unwrap())).unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn endpoints_only_answer_their_own_method() {
+    let server = TestServer::new(DemoRouter::new());
+
+    for method in ["DELETE", "HEAD", "PATCH"] {
+        let req = Request::builder().method(method).uri("/the_time").body(Bytes::new()).unwrap();
+        let res = common::runtime().block_on(server.request(req)).unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND, "{method} /the_time");
+    }
+}
+
+#[test]
+fn assets_still_take_priority_for_get() {
+    let server = TestServer::new(DemoRouter::new());
+
+    let res = common::runtime().block_on(server.request(Request::get("/").body(Bytes::new()).unwrap())).unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers().get("content-type").unwrap(), "text/html");
+}
+
+#[test]
+fn client_encodes_data_as_query_string() {
+    let client = DemoRouter::client_with_transport("http://test".into(), Default::default(), TestServer::new(DemoRouter::new()));
+
+    let greeting = common::runtime().block_on(client.greet(Greeting { name: "a&b=c".to_string(), shout: false })).unwrap();
+    assert_eq!(greeting, "Hello, a&b=c!");
+}
+
+#[test]
+fn malformed_query_is_a_bad_request() {
+    let server = TestServer::new(DemoRouter::new());
+
+    let res = common::runtime().block_on(server.request(Request::get("/greet?name=milrouter&shout=maybe").body(Bytes::new()).unwrap())).unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
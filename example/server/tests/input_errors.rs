@@ -0,0 +1,64 @@
+use {
+    milrouter::{
+        InputError, InputErrorKind,
+        bytes::Bytes,
+        hyper::{Request, StatusCode},
+        testing::{TestResponse, TestServer},
+    },
+    server::DemoRouter,
+};
+
+fn send(req: Request<Bytes>) -> TestResponse {
+    let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    rt.block_on(TestServer::new(DemoRouter::new()).request(req)).unwrap()
+}
+
+fn search(content_type: &str, body: &'static str) -> Request<Bytes> {
+    Request::post("/search").header("content-type", content_type).body(Bytes::from(body)).unwrap()
+}
+
+#[test]
+fn malformed_json_is_a_bad_request() {
+    let res = send(search("application/json", "{\"needle\": "));
+
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(res.headers().get("content-type").unwrap(), "application/json");
+    assert_eq!(res.json::<InputError>().unwrap().error, InputErrorKind::InvalidBody);
+}
+
+#[test]
+fn bad_request_names_the_failing_field() {
+    let res = send(search("application/json", r#"{"needle": "or", "haystack": ["router", 7]}"#));
+
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let err = res.json::<InputError>().unwrap();
+    assert_eq!(err.field.as_deref(), Some("haystack[1]"));
+    assert!(err.message.contains("expected a string"), "{}", err.message);
+}
+
+#[test]
+fn non_json_content_type_is_unsupported() {
+    let res = send(search("text/plain", r#"{"needle": "or", "haystack": []}"#));
+
+    assert_eq!(res.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    assert_eq!(res.json::<InputError>().unwrap().error, InputErrorKind::UnsupportedMediaType);
+}
+
+#[test]
+fn oversized_body_is_rejected() {
+    let body = format!(r#"{{"needle": "{}", "haystack": []}}"#, "x".repeat(milrouter::DEFAULT_MAX_BODY_SIZE));
+    let res = send(Request::post("/search").body(Bytes::from(body)).unwrap());
+
+    assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(res.json::<InputError>().unwrap().error, InputErrorKind::BodyTooLarge);
+}
+
+#[test]
+fn bad_query_names_the_failing_field() {
+    let res = send(Request::get("/greet?name=milrouter&shout=maybe").body(Bytes::new()).unwrap());
+
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let err = res.json::<InputError>().unwrap();
+    assert_eq!(err.error, InputErrorKind::InvalidQuery);
+    assert_eq!(err.field.as_deref(), Some("shout"));
+}
@@ -0,0 +1,61 @@
+mod common;
+
+use {
+    milrouter::{
+        Mount,
+        Router,
+        WithState,
+        bytes::Bytes,
+        hyper::{Request, StatusCode},
+        testing::{TestResponse, TestServer},
+    },
+    server::{DemoRouter, Greeting},
+};
+
+fn mounted() -> TestServer<Mount<WithState<DemoRouter, ()>>> { TestServer::new(Mount::new("/api/v1/", DemoRouter::new())) }
+
+fn put(server: &TestServer<Mount<WithState<DemoRouter, ()>>>, path: &str) -> TestResponse {
+    common::runtime().block_on(server.request(Request::put(path).body(Bytes::new()).unwrap())).unwrap()
+}
+
+#[test]
+fn prefix_is_stripped_before_matching() {
+    let server = mounted();
+
+    assert_eq!(put(&server, "/api/v1/the_time").status(), StatusCode::OK);
+    assert_eq!(put(&server, "/api/v1/repeat/ab/3").json::<String>().unwrap(), "ababab");
+
+    let greet = Request::get("/api/v1/greet?name=mount").body(Bytes::new()).unwrap();
+    let greet = common::runtime().block_on(server.request(greet)).unwrap();
+    assert_eq!(greet.json::<String>().unwrap(), "Hello, mount!");
+}
+
+#[test]
+fn paths_outside_the_prefix_are_not_found() {
+    let server = mounted();
+
+    for path in ["/the_time", "/api/the_time", "/api/v1the_time", "/api/v2/the_time"] {
+        let res = put(&server, path);
+        assert_eq!(res.status(), StatusCode::NOT_FOUND, "{path}");
+        // Outside the mount, the router's middleware doesn't run either.
+        assert!(res.headers().get("access-control-allow-origin").is_none(), "{path}");
+    }
+}
+
+#[test]
+fn clients_include_the_prefix_in_their_host() {
+    let client = DemoRouter::client_with_transport("http://test/api/v1".into(), Default::default(), mounted());
+
+    assert_eq!(common::runtime().block_on(client.add(2, 40)).unwrap(), 42);
+    let greeting = Greeting { name: "client".into(), shout: true };
+    assert_eq!(common::runtime().block_on(client.greet(greeting)).unwrap(), "HELLO, CLIENT!");
+}
+
+#[test]
+fn routes_are_listed_under_the_prefix() {
+    let routes = Mount::new("api/v1", DemoRouter::new()).routes();
+
+    assert_eq!(routes.len(), DemoRouter::new().routes().len());
+    assert!(routes.iter().all(|r| r.path.starts_with("/api/v1/")));
+    assert!(routes.iter().any(|r| r.path == "/api/v1/repeat/{word}/{times}"));
+}
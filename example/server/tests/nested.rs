@@ -0,0 +1,74 @@
+mod common;
+
+use {
+    milrouter::{
+        bytes::Bytes,
+        hyper::{HeaderMap, Request, StatusCode, header::HeaderValue},
+        testing::{TestResponse, TestServer},
+    },
+    server::DemoRouter,
+};
+
+fn admin_headers() -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert("authorization", HeaderValue::from_static("Bearer admin"));
+    headers
+}
+
+fn send(req: Request<Bytes>) -> TestResponse { common::runtime().block_on(TestServer::new(DemoRouter::new()).request(req)).unwrap() }
+
+#[test]
+fn nested_clients_reach_the_child_router() {
+    let client = DemoRouter::client_with_transport("http://test".into(), admin_headers(), TestServer::new(DemoRouter::new()));
+    let rt = common::runtime();
+
+    assert_eq!(rt.block_on(client.admin().ban_user("mallory".into())).unwrap(), "mallory has been banned");
+    assert_eq!(rt.block_on(client.admin().admin_user(7)).unwrap(), "user #7");
+    // The parent's own endpoints are still there.
+    assert_eq!(rt.block_on(client.add(1, 1)).unwrap(), 2);
+}
+
+#[test]
+fn child_auth_still_applies() {
+    let client = DemoRouter::client_with_transport("http://test".into(), HeaderMap::new(), TestServer::new(DemoRouter::new()));
+
+    let err = common::runtime().block_on(client.admin().ban_user("mallory".into())).unwrap_err();
+    assert!(err.to_string().contains("401"), "{err}");
+}
+
+#[test]
+fn child_middleware_stacks_on_the_parents() {
+    let req = Request::post("/admin/ban_user")
+        .header("origin", "https://admin.example")
+        .header("authorization", "Bearer admin")
+        .body(Bytes::from_static(b"\"mallory\""))
+        .unwrap();
+    let res = send(req);
+
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers()["access-control-allow-origin"], "https://admin.example");
+    assert_eq!(res.headers()["x-audited"], "admin");
+
+    let parent = send(Request::put("/the_time").body(Bytes::new()).unwrap());
+    assert!(parent.headers().get("x-audited").is_none());
+}
+
+#[test]
+fn nested_routers_are_reached_under_static() {
+    let req = Request::post("/static/admin/ban_user")
+        .header("authorization", "Bearer admin")
+        .body(Bytes::from_static(b"\"mallory\""))
+        .unwrap();
+    let res = send(req);
+
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers()["x-audited"], "admin");
+}
+
+#[test]
+fn unknown_paths_under_the_prefix_are_not_found() {
+    for path in ["/admin", "/admin/the_time", "/administrator/ban_user"] {
+        let res = send(Request::put(path).body(Bytes::new()).unwrap());
+        assert_eq!(res.status(), StatusCode::NOT_FOUND, "{path}");
+    }
+}
@@ -0,0 +1,92 @@
+use {
+    milrouter::{
+        Mount,
+        bytes::Bytes,
+        hyper::{Request, StatusCode},
+        serde_json::{Value, json},
+        testing::TestServer,
+    },
+    server::DemoRouter,
+};
+
+#[test]
+fn document_lists_every_endpoint() {
+    let doc = DemoRouter::openapi();
+
+    assert_eq!(doc["openapi"], "3.1.0");
+    assert_eq!(doc["info"]["title"], "DemoRouter");
+
+    let paths = doc["paths"].as_object().unwrap();
+    let mut routes = paths
+        .iter()
+        .flat_map(|(path, item)| item.as_object().unwrap().keys().map(move |method| format!("{method} {path}")))
+        .collect::<Vec<_>>();
+    routes.sort();
+
+    assert_eq!(routes, vec![
+        "get /admin/users/{id}",
+        "get /define/{word}",
+        "get /greet",
+        "post /add",
+        "post /admin/ban_user",
+        "post /clients",
+        "post /search",
+        "post /version_blob",
+        "put /repeat/{word}/{times}",
+        "put /request_id",
+        "put /the_time",
+        "put /visit",
+    ]);
+}
+
+#[test]
+fn schemas_are_shared_through_components() {
+    let doc = DemoRouter::openapi();
+    let search = &doc["paths"]["/search"]["post"];
+
+    assert_eq!(search["requestBody"]["content"]["application/json"]["schema"], json!({ "$ref": "#/components/schemas/SearchQuery" }));
+    assert_eq!(search["responses"]["200"]["content"]["application/json"]["schema"], json!({ "$ref": "#/components/schemas/SearchResult" }));
+    assert!(doc["components"]["schemas"]["SearchQuery"]["properties"]["haystack"].is_object());
+}
+
+#[test]
+fn parameters_and_errors_are_described() {
+    let doc = DemoRouter::openapi();
+
+    let repeat = &doc["paths"]["/repeat/{word}/{times}"]["put"];
+    let params = repeat["parameters"].as_array().unwrap();
+    assert_eq!(params.iter().map(|p| p["name"].as_str().unwrap()).collect::<Vec<_>>(), vec!["word", "times"]);
+    assert_eq!(params[1]["in"], "path");
+    assert_eq!(params[1]["schema"]["type"], "integer");
+    assert!(repeat.get("requestBody").is_none());
+
+    let greet = &doc["paths"]["/greet"]["get"];
+    assert_eq!(greet["parameters"][0]["in"], "query");
+
+    let define = &doc["paths"]["/define/{word}"]["get"];
+    assert_eq!(define["responses"]["default"]["content"]["application/json"]["schema"], json!({ "$ref": "#/components/schemas/DefineError" }));
+
+    let blob = &doc["paths"]["/version_blob"]["post"];
+    assert!(blob["responses"]["200"]["content"]["application/octet-stream"].is_object());
+}
+
+#[test]
+fn document_is_served_at_the_configured_path() {
+    let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let server = TestServer::new(DemoRouter::new());
+
+    let res = rt.block_on(server.request(Request::get("/openapi.json").body(Bytes::new()).unwrap())).unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.json::<Value>().unwrap(), DemoRouter::openapi());
+}
+
+#[test]
+fn mounted_document_names_the_prefix_as_its_server() {
+    let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let server = TestServer::new(Mount::new("/api/v1", DemoRouter::new()));
+
+    let res = rt.block_on(server.request(Request::get("/api/v1/openapi.json").body(Bytes::new()).unwrap())).unwrap();
+    let doc = res.json::<Value>().unwrap();
+    assert_eq!(doc["servers"], json!([{ "url": "/api/v1" }]));
+    assert_eq!(doc["paths"], DemoRouter::openapi()["paths"]);
+}
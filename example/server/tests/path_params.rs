@@ -0,0 +1,44 @@
+mod common;
+
+use {
+    milrouter::{
+        bytes::Bytes,
+        hyper::{Request, StatusCode},
+        testing::TestServer,
+    },
+    server::DemoRouter,
+};
+
+#[test]
+fn path_segments_are_passed_to_the_handler() {
+    let server = TestServer::new(DemoRouter::new());
+
+    let res = common::runtime().block_on(server.request(Request::put("/repeat/ab/3").body(Bytes::new()).unwrap())).unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.json::<String>().unwrap(), "ababab");
+}
+
+#[test]
+fn unparseable_segment_is_a_bad_request() {
+    let server = TestServer::new(DemoRouter::new());
+
+    let res = common::runtime().block_on(server.request(Request::put("/repeat/ab/lots").body(Bytes::new()).unwrap())).unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    assert!(res.text().unwrap().contains("times"));
+}
+
+#[test]
+fn routed_endpoint_is_not_served_at_its_variant_name() {
+    let server = TestServer::new(DemoRouter::new());
+
+    let res = common::runtime().block_on(server.request(Request::put("/repeat").body(Bytes::new()).unwrap())).unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn client_fills_and_encodes_path_parameters() {
+    let client = DemoRouter::client_with_transport("http://test".into(), Default::default(), TestServer::new(DemoRouter::new()));
+
+    let repeated = common::runtime().block_on(client.repeat("a b/c?".to_string(), 2)).unwrap();
+    assert_eq!(repeated, "a b/c?a b/c?");
+}
@@ -1,16 +1,12 @@
-use {
-    server::{DemoRouter, SearchQuery},
-    std::time::Duration,
-};
+use server::{DemoRouter, SearchQuery};
 
 #[test]
 fn typed_client_roundtrip_covers_query_tools() -> Result<(), Box<dyn std::error::Error>> {
-    let probe = std::net::TcpListener::bind("127.0.0.1:0")?;
-    let addr = probe.local_addr()?;
-    drop(probe);
+    let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+    let addr = listener.local_addr()?;
 
     std::thread::spawn(move || {
-        let _ = milrouter::serve_local(addr, DemoRouter::new());
+        let _ = milrouter::serve_local_listener(listener, DemoRouter::new());
     });
 
     let mut headers = hyper::HeaderMap::new();
@@ -19,24 +15,7 @@ fn typed_client_roundtrip_covers_query_tools() -> Result<(), Box<dyn std::error:
 
     let rt = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
 
-    let mut server_ready = false;
-    let mut last_error = String::new();
-    for _ in 0..40 {
-        match rt.block_on(client.the_time(())) {
-            Ok(_) => {
-                server_ready = true;
-                break;
-            }
-            Err(e) => {
-                last_error = e.to_string();
-            }
-        }
-        std::thread::sleep(Duration::from_millis(25));
-    }
-
-    if !server_ready {
-        return Err(format!("Server did not become ready in time: {last_error}").into());
-    }
+    rt.block_on(client.the_time())?;
 
     let result = rt.block_on(client.search(SearchQuery {
         needle: "or".to_string(),
@@ -46,7 +25,7 @@ fn typed_client_roundtrip_covers_query_tools() -> Result<(), Box<dyn std::error:
     assert_eq!(result.total, 1);
     assert_eq!(result.matches, vec!["orbit".to_string()]);
 
-    let raw = rt.block_on(client.version_blob(()))?;
+    let raw = rt.block_on(client.version_blob())?;
     assert_eq!(raw, b"milrouter-demo-v2\n".to_vec());
 
     Ok(())
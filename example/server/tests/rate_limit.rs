@@ -0,0 +1,63 @@
+mod common;
+
+use {
+    milrouter::{
+        bytes::Bytes,
+        hyper::{Request, StatusCode},
+        testing::TestServer,
+    },
+    server::{DemoRouter, SearchQuery},
+};
+
+fn search(ip: &str) -> Request<Bytes> {
+    let query = SearchQuery { needle: "a".into(), haystack: vec!["abc".into()] };
+    Request::post("/search")
+        .header("x-forwarded-for", ip)
+        .body(Bytes::from(milrouter::serde_json::to_vec(&query).unwrap()))
+        .unwrap()
+}
+
+#[test]
+fn rate_limit_persists_across_requests() {
+    common::runtime().block_on(async {
+        let server = TestServer::new(DemoRouter::new());
+        for _ in 0..10 {
+            assert_eq!(server.request(search("203.0.113.7")).await.unwrap().status(), StatusCode::OK);
+        }
+
+        let limited = server.request(search("203.0.113.7")).await.unwrap();
+        assert_eq!(limited.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(limited.headers()["retry-after"], "60");
+
+        // Other clients have their own budget.
+        assert_eq!(server.request(search("203.0.113.8")).await.unwrap().status(), StatusCode::OK);
+    });
+}
+
+#[test]
+fn routers_keep_their_own_rate_limits() {
+    common::runtime().block_on(async {
+        let first = TestServer::new(DemoRouter::new());
+        for _ in 0..10 {
+            assert_eq!(first.request(search("203.0.113.9")).await.unwrap().status(), StatusCode::OK);
+        }
+        assert_eq!(first.request(search("203.0.113.9")).await.unwrap().status(), StatusCode::TOO_MANY_REQUESTS);
+
+        // Another router value has its own middleware, and so its own counters.
+        let second = TestServer::new(DemoRouter::new());
+        assert_eq!(second.request(search("203.0.113.9")).await.unwrap().status(), StatusCode::OK);
+    });
+}
+
+#[test]
+fn request_context_is_not_shared_between_requests() {
+    common::runtime().block_on(async {
+        let server = TestServer::new(DemoRouter::new());
+        let first = Request::put("/the_time").header("origin", "https://a.example").body(Bytes::new()).unwrap();
+        let first = server.request(first).await.unwrap();
+        assert_eq!(first.headers()["access-control-allow-origin"], "https://a.example");
+
+        let second = server.request(Request::put("/the_time").body(Bytes::new()).unwrap()).await.unwrap();
+        assert_eq!(second.headers()["access-control-allow-origin"], "*");
+    });
+}
@@ -0,0 +1,43 @@
+mod common;
+
+use {
+    milrouter::{
+        bytes::Bytes,
+        hyper::{Request, StatusCode},
+        testing::{TestResponse, TestServer},
+    },
+    server::{DemoRouter, SearchQuery},
+};
+
+fn send(req: Request<Bytes>) -> TestResponse { common::runtime().block_on(TestServer::new(DemoRouter::new()).request(req)).unwrap() }
+
+#[test]
+fn group_middleware_runs_for_its_variants_only() {
+    let query = SearchQuery { needle: "a".into(), haystack: vec!["abc".into()] };
+    let search = send(Request::post("/search").body(Bytes::from(milrouter::serde_json::to_vec(&query).unwrap())).unwrap());
+    assert_eq!(search.status(), StatusCode::OK);
+    assert_eq!(search.headers()["x-ratelimit-limit"], "10");
+
+    let the_time = send(Request::put("/the_time").body(Bytes::new()).unwrap());
+    assert_eq!(the_time.status(), StatusCode::OK);
+    assert!(the_time.headers().get("x-ratelimit-limit").is_none());
+}
+
+#[test]
+fn endpoint_middleware_runs_for_that_endpoint_only() {
+    let blob = send(Request::post("/version_blob").body(Bytes::new()).unwrap());
+    assert_eq!(blob.headers()["cache-control"], "public, max-age=3600");
+
+    let the_time = send(Request::put("/the_time").body(Bytes::new()).unwrap());
+    assert!(the_time.headers().get("cache-control").is_none());
+}
+
+#[test]
+fn router_middleware_still_runs_everywhere() {
+    let blob = send(Request::post("/version_blob").header("origin", "https://example.com").body(Bytes::new()).unwrap());
+    assert_eq!(blob.headers()["access-control-allow-origin"], "https://example.com");
+
+    let asset = send(Request::get("/").body(Bytes::new()).unwrap());
+    assert!(asset.headers().get("access-control-allow-origin").is_some());
+    assert!(asset.headers().get("x-ratelimit-limit").is_none());
+}
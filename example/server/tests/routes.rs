@@ -0,0 +1,74 @@
+use {
+    milrouter::{Endpoint, Method, RouteDescriptor, RouteMode, Router},
+    server::DemoRouter,
+};
+
+/// An endpoint implemented by hand, relying on the trait's defaults.
+struct Ping;
+
+impl Endpoint<()> for Ping {
+    type Data = ();
+    type Error = milrouter::anyhow::Error;
+    type Returns = String;
+
+    fn is_idempotent() -> bool { true }
+
+    fn path() -> &'static str { "ping" }
+}
+
+#[test]
+fn every_endpoint_is_listed_in_declaration_order() {
+    let routes = DemoRouter::new()
+        .routes()
+        .into_iter()
+        .map(|r| format!("{} {}", r.method, r.path))
+        .collect::<Vec<_>>();
+
+    assert_eq!(routes, vec![
+        "PUT /the_time",
+        "POST /search",
+        "POST /version_blob",
+        "PUT /repeat/{word}/{times}",
+        "GET /greet",
+        "GET /define/{word}",
+        "PUT /visit",
+        "POST /clients",
+        "POST /add",
+        "PUT /request_id",
+        "POST /admin/ban_user",
+        "GET /admin/users/{id}",
+    ]);
+}
+
+#[test]
+fn descriptors_carry_types_mode_and_auth() {
+    let routes = DemoRouter::new().routes();
+    let find = |path: &str| routes.iter().find(|r| r.path == path).cloned().unwrap();
+
+    assert_eq!(find("/search"), RouteDescriptor {
+        path: "/search".into(),
+        method: Method::POST,
+        mode: RouteMode::Json,
+        input: "SearchQuery",
+        output: "SearchResult",
+        auth: "auth_handler",
+    });
+
+    let blob = find("/version_blob");
+    assert_eq!((blob.mode, blob.input, blob.output), (RouteMode::Raw, "()", "Vec<u8>"));
+
+    let greet = find("/greet");
+    assert_eq!((greet.input, greet.auth), ("Greeting", "milrouter::all_aboard"));
+}
+
+#[test]
+fn hand_written_endpoints_get_a_default_descriptor() {
+    assert_eq!(<Ping as Endpoint<()>>::descriptor("ping"), RouteDescriptor {
+        path: "/ping".into(),
+        method: Method::PUT,
+        mode: RouteMode::Json,
+        input: "()",
+        output: std::any::type_name::<String>(),
+        auth: "",
+    });
+}
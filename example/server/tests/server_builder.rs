@@ -0,0 +1,153 @@
+use {
+    milrouter::{Protocol, ServerBuilder, WithState, reqwest},
+    server::{DemoRouter, SearchQuery},
+    std::{
+        io::{Read, Write},
+        net::{SocketAddr, TcpStream},
+        time::Duration,
+    },
+};
+
+fn spawn(builder: ServerBuilder<WithState<DemoRouter, ()>>) -> (SocketAddr, tokio::sync::oneshot::Sender<()>) {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+    std::thread::spawn(move || {
+        builder.serve_local_listener_with_shutdown(listener, async move {
+            let _ = rx.await;
+        })
+    });
+
+    (addr, tx)
+}
+
+#[test]
+fn header_read_timeout_closes_slow_connections() {
+    let (addr, _shutdown) = spawn(ServerBuilder::new(DemoRouter::new()).header_read_timeout(Duration::from_millis(200)));
+
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    stream.write_all(b"PUT /the_time HTTP/1.1\r\nHost: localhost\r\n").unwrap();
+
+    // The request head is never finished, so the server should give up on us.
+    let mut buf = Vec::new();
+    let read = stream.read_to_end(&mut buf);
+    assert!(read.is_ok(), "Connection was not closed by the server: {read:?}");
+}
+
+#[test]
+fn max_connections_holds_back_extra_clients() {
+    let (addr, _shutdown) = spawn(
+        ServerBuilder::new(DemoRouter::new()).max_connections(1).header_read_timeout(Duration::from_secs(30)),
+    );
+
+    // Hold the only slot open without ever sending a request.
+    let hog = TcpStream::connect(addr).unwrap();
+    std::thread::sleep(Duration::from_millis(100));
+
+    let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let client = DemoRouter::client(format!("http://{addr}"), Default::default());
+
+    let blocked = rt.block_on(async { tokio::time::timeout(Duration::from_millis(300), client.the_time()).await });
+    assert!(blocked.is_err(), "Request was served while the connection limit was reached");
+
+    drop(hog);
+    let served = rt.block_on(async { tokio::time::timeout(Duration::from_secs(5), client.the_time()).await });
+    assert!(matches!(served, Ok(Ok(_))), "Request was not served after a slot freed up: {served:?}");
+}
+
+#[test]
+#[should_panic(expected = "max_connections must be at least 1")]
+fn zero_max_connections_is_rejected() { let _ = ServerBuilder::new(DemoRouter::new()).max_connections(0); }
+
+#[test]
+#[should_panic(expected = "max_buf_size must be at least 8192 bytes")]
+fn tiny_max_buf_size_is_rejected() { let _ = ServerBuilder::new(DemoRouter::new()).max_buf_size(1024); }
+
+fn put_the_time(rt: &tokio::runtime::Runtime, client: &reqwest::Client, addr: SocketAddr) -> reqwest::Response {
+    rt.block_on(client.put(format!("http://{addr}/the_time")).send()).unwrap()
+}
+
+#[test]
+fn http2_prior_knowledge_is_served() {
+    let (addr, _shutdown) = spawn(ServerBuilder::new(DemoRouter::new()).protocol(Protocol::Http2));
+
+    let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let h2 = reqwest::Client::builder().http2_prior_knowledge().build().unwrap();
+
+    let resp = put_the_time(&rt, &h2, addr);
+    assert_eq!(resp.version(), reqwest::Version::HTTP_2);
+    assert!(resp.status().is_success());
+}
+
+#[test]
+fn auto_protocol_accepts_http1_and_http2() {
+    let (addr, _shutdown) = spawn(ServerBuilder::new(DemoRouter::new()).protocol(Protocol::Auto));
+
+    let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let h1 = reqwest::Client::builder().http1_only().build().unwrap();
+    let h2 = reqwest::Client::builder().http2_prior_knowledge().build().unwrap();
+
+    let resp = put_the_time(&rt, &h1, addr);
+    assert_eq!(resp.version(), reqwest::Version::HTTP_11);
+    assert!(resp.status().is_success());
+
+    let resp = put_the_time(&rt, &h2, addr);
+    assert_eq!(resp.version(), reqwest::Version::HTTP_2);
+    assert!(resp.status().is_success());
+}
+
+#[test]
+fn max_body_size_is_enforced_per_server() {
+    let (addr, _shutdown) = spawn(ServerBuilder::new(DemoRouter::new()).max_body_size(64));
+
+    let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let client = DemoRouter::client(format!("http://{addr}"), Default::default());
+
+    let small = rt.block_on(client.search(SearchQuery { needle: "a".to_string(), haystack: vec!["abc".to_string()] }));
+    assert!(small.is_ok(), "{small:?}");
+
+    let large = rt.block_on(client.search(SearchQuery { needle: "a".to_string(), haystack: vec!["abc".repeat(64)] }));
+    assert!(large.unwrap_err().to_string().starts_with("413"));
+}
+
+#[test]
+fn shutdown_drops_connections_still_open_after_the_grace_period() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    listener.set_nonblocking(true).unwrap();
+
+    let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+    let (served_tx, served_rx) = std::sync::mpsc::channel();
+    // The runtime outlives the server, so only the server itself can close the connection.
+    std::thread::spawn(move || {
+        let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        rt.block_on(async move {
+            let listener = tokio::net::TcpListener::from_std(listener).unwrap();
+            let served = ServerBuilder::new(DemoRouter::new())
+                .shutdown_grace(Duration::from_millis(200))
+                .serve_listener_with_shutdown(listener, async move {
+                    let _ = rx.await;
+                })
+                .await;
+            served_tx.send(served.is_ok()).unwrap();
+            std::future::pending::<()>().await
+        })
+    });
+
+    // The body never arrives, so the request is still in flight when the grace period ends.
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream.write_all(b"POST /search HTTP/1.1\r\nHost: localhost\r\nContent-Length: 100\r\n\r\n{").unwrap();
+    std::thread::sleep(Duration::from_millis(100));
+
+    let _ = tx.send(());
+    assert!(served_rx.recv_timeout(Duration::from_secs(5)).unwrap(), "server returned an error while shutting down");
+
+    stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let read = stream.read_to_end(&mut Vec::new());
+    assert!(
+        !matches!(&read, Err(e) if matches!(e.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut)),
+        "Connection outlived the grace period: {read:?}"
+    );
+}
@@ -0,0 +1,24 @@
+#![cfg(unix)]
+
+// The only test in this binary, so nothing else reads the environment while it changes.
+#[test]
+fn activation_environment_is_cleared_only_on_request() {
+    // SAFETY: no other threads touch the environment during this test.
+    unsafe {
+        std::env::set_var("LISTEN_PID", "1");
+        std::env::set_var("LISTEN_FDS", "1");
+        std::env::set_var("LISTEN_FDNAMES", "api");
+    }
+
+    // Addressed to another process, so nothing is handed out, and the environment stays as it was.
+    assert!(milrouter::listen_fds_with_names().is_empty());
+    for var in ["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"] {
+        assert!(std::env::var_os(var).is_some(), "{var} was removed from the environment");
+    }
+
+    // SAFETY: as above.
+    unsafe { milrouter::unset_listen_environment() };
+    for var in ["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"] {
+        assert!(std::env::var_os(var).is_none(), "{var} was left in the environment");
+    }
+}
@@ -0,0 +1,49 @@
+#![cfg(unix)]
+
+use std::{
+    net::TcpListener,
+    os::{fd::AsRawFd, unix::process::CommandExt},
+    process::Command,
+};
+
+const CHILD: &str = "MILROUTER_SOCKET_ACTIVATION_CHILD";
+
+// The only test in this binary.  It runs itself again as a child process holding three sockets
+// at fds 3 to 5, the way systemd passes them, with the middle one unnamed.
+#[test]
+fn empty_names_keep_their_descriptor() {
+    if std::env::var_os(CHILD).is_some() {
+        // SAFETY: the child runs only this test, so nothing else touches the environment.
+        unsafe { std::env::set_var("LISTEN_PID", std::process::id().to_string()) };
+        let names = milrouter::listen_fds_with_names().into_iter().map(|(name, _)| name).collect::<Vec<_>>();
+        assert_eq!(names, ["a", "unknown", "c"]);
+        return;
+    }
+
+    let listeners = (0..3).map(|_| TcpListener::bind("127.0.0.1:0").unwrap()).collect::<Vec<_>>();
+    // Copies above the target range, so moving one onto fd 3 to 5 can't close another first.
+    // SAFETY: `fcntl` only duplicates a descriptor these listeners own.
+    let fds = listeners.iter().map(|l| unsafe { libc::fcntl(l.as_raw_fd(), libc::F_DUPFD_CLOEXEC, 100) }).collect::<Vec<_>>();
+    assert!(fds.iter().all(|&fd| fd >= 100));
+
+    let mut child = Command::new(std::env::current_exe().unwrap());
+    child
+        .args(["--exact", "empty_names_keep_their_descriptor", "--test-threads=1"])
+        .env(CHILD, "1")
+        .env("LISTEN_FDS", "3")
+        .env("LISTEN_FDNAMES", "a::c");
+    // SAFETY: `dup2` is async-signal-safe, and the copies it makes don't carry close-on-exec.
+    unsafe {
+        child.pre_exec(move || {
+            for (target, &fd) in fds.iter().enumerate() {
+                if libc::dup2(fd, 3 + target as i32) < 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            Ok(())
+        });
+    }
+
+    let output = child.output().unwrap();
+    assert!(output.status.success(), "child failed:\n{}", String::from_utf8_lossy(&output.stdout));
+}
@@ -0,0 +1,42 @@
+mod common;
+
+use {
+    milrouter::{
+        bytes::Bytes,
+        hyper::{Request, StatusCode},
+        testing::{TestResponse, TestServer},
+    },
+    server::{AppState, DemoRouter},
+};
+
+fn visit<R: milrouter::Router + Send + Sync + 'static>(server: &TestServer<R>) -> TestResponse {
+    common::runtime().block_on(server.request(Request::put("/visit").body(Bytes::new()).unwrap())).unwrap()
+}
+
+#[test]
+fn state_is_shared_across_requests() {
+    let server = TestServer::new(DemoRouter::with_state(AppState::default()));
+    let client = DemoRouter::client_with_transport("http://test".into(), Default::default(), server);
+
+    let rt = common::runtime();
+    assert_eq!(rt.block_on(client.visit()).unwrap(), 1);
+    assert_eq!(rt.block_on(client.visit()).unwrap(), 2);
+}
+
+#[test]
+fn each_router_gets_its_own_state() {
+    let first = TestServer::new(DemoRouter::with_state(AppState::default()));
+    let second = TestServer::new(DemoRouter::with_state(AppState::default()));
+
+    assert_eq!(visit(&first).json::<usize>().unwrap(), 1);
+    assert_eq!(visit(&first).json::<usize>().unwrap(), 2);
+    assert_eq!(visit(&second).json::<usize>().unwrap(), 1);
+}
+
+#[test]
+fn missing_state_is_a_server_error() {
+    let res = visit(&TestServer::new(DemoRouter::new()));
+
+    assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert!(res.text().unwrap().is_empty());
+}
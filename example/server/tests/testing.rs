@@ -0,0 +1,62 @@
+mod common;
+
+use {
+    milrouter::{
+        bytes::Bytes,
+        hyper::{Request, StatusCode},
+        testing::TestServer,
+    },
+    server::{DemoRouter, SearchQuery, SearchResult},
+};
+
+fn search_body() -> Bytes {
+    Bytes::from(
+        milrouter::serde_json::to_vec(&SearchQuery {
+            needle: "or".to_string(),
+            haystack: vec!["router".to_string(), "planet".to_string(), "orbit".to_string()],
+        })
+        .unwrap(),
+    )
+}
+
+#[test]
+fn unknown_path_is_not_found() {
+    let server = TestServer::new(DemoRouter::new());
+
+    let res = common::runtime().block_on(server.request(Request::put("/nope").body(Bytes::new()).unwrap())).unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn auth_rejection_is_unauthorized() {
+    let server = TestServer::new(DemoRouter::new());
+
+    let req = Request::post("/search").header("evil", "1").body(search_body()).unwrap();
+    let res = common::runtime().block_on(server.request(req)).unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn endpoint_response_is_decoded_and_middleware_runs() {
+    let server = TestServer::new(DemoRouter::new());
+
+    let req = Request::post("/search").header("origin", "https://example.com").body(search_body()).unwrap();
+    let res = common::runtime().block_on(server.request(req)).unwrap();
+
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers().get("access-control-allow-origin").unwrap(), "https://example.com");
+
+    let result = res.json::<SearchResult>().unwrap();
+    assert_eq!(result.matches, vec!["orbit".to_string()]);
+}
+
+#[test]
+fn typed_client_runs_in_process() {
+    let server = TestServer::new(DemoRouter::new());
+    let client = DemoRouter::client_with_transport("http://test".into(), Default::default(), server);
+
+    let result = common::runtime()
+        .block_on(client.search(SearchQuery { needle: "b".to_string(), haystack: vec!["orbit".to_string(), "x".to_string()] }))
+        .unwrap();
+    assert_eq!(result.total, 1);
+}
@@ -0,0 +1,113 @@
+use {
+    milrouter::{Protocol, ServerBuilder, WithState, reqwest},
+    rcgen::{BasicConstraints, CertificateParams, IsCa, KeyPair},
+    server::{DemoRouter, SearchQuery},
+    std::{net::SocketAddr, path::PathBuf},
+};
+
+/// A throwaway CA plus a `localhost` leaf certificate signed by it, written to a temp directory.
+struct TestPki {
+    dir: PathBuf,
+    ca_pem: String,
+}
+
+impl TestPki {
+    fn generate(name: &str) -> Self {
+        let ca_key = KeyPair::generate().unwrap();
+        let mut ca_params = CertificateParams::new(Vec::<String>::new()).unwrap();
+        ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let ca = ca_params.self_signed(&ca_key).unwrap();
+
+        let leaf_key = KeyPair::generate().unwrap();
+        let leaf = CertificateParams::new(vec!["localhost".to_string()]).unwrap().signed_by(&leaf_key, &ca, &ca_key).unwrap();
+
+        let dir = std::env::temp_dir().join(format!("milrouter-tls-{name}-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("cert.pem"), leaf.pem()).unwrap();
+        std::fs::write(dir.join("key.pem"), leaf_key.serialize_pem()).unwrap();
+
+        Self { dir, ca_pem: ca.pem() }
+    }
+
+    fn http_client(&self) -> reqwest::Client {
+        reqwest::Client::builder()
+            .use_rustls_tls()
+            .add_root_certificate(reqwest::Certificate::from_pem(self.ca_pem.as_bytes()).unwrap())
+            .build()
+            .unwrap()
+    }
+}
+
+impl Drop for TestPki {
+    fn drop(&mut self) { let _ = std::fs::remove_dir_all(&self.dir); }
+}
+
+fn spawn(builder: ServerBuilder<WithState<DemoRouter, ()>>) -> (SocketAddr, tokio::sync::oneshot::Sender<()>) {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+    std::thread::spawn(move || {
+        builder.serve_local_listener_with_shutdown(listener, async move {
+            let _ = rx.await;
+        })
+    });
+
+    (addr, tx)
+}
+
+#[test]
+fn typed_client_trusts_custom_root() {
+    let pki = TestPki::generate("client");
+    let builder = ServerBuilder::new(DemoRouter::new()).tls_pem(pki.dir.join("cert.pem"), pki.dir.join("key.pem")).unwrap();
+    let (addr, _shutdown) = spawn(builder);
+
+    let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let client = DemoRouter::client_with(format!("https://localhost:{}", addr.port()), Default::default(), pki.http_client());
+
+    let result = rt
+        .block_on(client.search(SearchQuery { needle: "or".to_string(), haystack: vec!["orbit".to_string()] }))
+        .unwrap();
+    assert_eq!(result.total, 1);
+
+    // Without the custom root the certificate must be rejected.
+    let untrusted = DemoRouter::client(format!("https://localhost:{}", addr.port()), Default::default());
+    assert!(rt.block_on(untrusted.the_time()).is_err());
+}
+
+#[test]
+fn alpn_negotiates_http2_over_tls() {
+    let pki = TestPki::generate("alpn");
+    let builder = ServerBuilder::new(DemoRouter::new())
+        .protocol(Protocol::Auto)
+        .tls_pem(pki.dir.join("cert.pem"), pki.dir.join("key.pem"))
+        .unwrap();
+    let (addr, _shutdown) = spawn(builder);
+
+    let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let resp = rt.block_on(pki.http_client().put(format!("https://localhost:{}/the_time", addr.port())).send()).unwrap();
+
+    assert_eq!(resp.version(), reqwest::Version::HTTP_2);
+    assert!(resp.status().is_success());
+}
+
+#[test]
+fn stalled_handshakes_give_up_their_connection_slot() {
+    let pki = TestPki::generate("stall");
+    let builder = ServerBuilder::new(DemoRouter::new())
+        .max_connections(1)
+        .tls_handshake_timeout(std::time::Duration::from_millis(200))
+        .tls_pem(pki.dir.join("cert.pem"), pki.dir.join("key.pem"))
+        .unwrap();
+    let (addr, _shutdown) = spawn(builder);
+
+    // Take the only slot and never send a ClientHello.
+    let mut hog = std::net::TcpStream::connect(addr).unwrap();
+    hog.set_read_timeout(Some(std::time::Duration::from_secs(5))).unwrap();
+    let mut buf = Vec::new();
+    assert!(std::io::Read::read_to_end(&mut hog, &mut buf).is_ok(), "Stalled handshake was never dropped");
+
+    let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let resp = rt.block_on(pki.http_client().put(format!("https://localhost:{}/the_time", addr.port())).send()).unwrap();
+    assert!(resp.status().is_success());
+}
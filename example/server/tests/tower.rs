@@ -0,0 +1,104 @@
+mod common;
+
+use {
+    milrouter::{
+        ServerBuilder,
+        bytes::Bytes,
+        http_body_util::{Full, StreamBody},
+        hyper::{Request, Response, StatusCode, body::Frame, header::HeaderValue},
+        hyper_util::{rt::TokioIo, server::conn::auto, service::TowerToHyperService},
+        reqwest,
+        testing::TestServer,
+        tower::{Layered, RouterService},
+    },
+    server::DemoRouter,
+    std::net::SocketAddr,
+    tower::limit::ConcurrencyLimitLayer,
+    tower_http::set_header::SetResponseHeaderLayer,
+};
+
+fn powered_by() -> SetResponseHeaderLayer<HeaderValue> {
+    SetResponseHeaderLayer::overriding(milrouter::hyper::header::SERVER, HeaderValue::from_static("milrouter"))
+}
+
+#[test]
+fn layers_wrap_the_router_in_serve() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let builder = ServerBuilder::new(DemoRouter::new()).layer(ConcurrencyLimitLayer::new(8)).layer(powered_by());
+
+    let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+    std::thread::spawn(move || {
+        builder.serve_local_listener_with_shutdown(listener, async move {
+            let _ = rx.await;
+        })
+    });
+
+    let rt = common::runtime();
+    let res = rt.block_on(reqwest::Client::new().put(format!("http://{addr}/the_time")).send()).unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers()["server"], "milrouter");
+    // The router's own middleware still runs inside the layers.
+    assert_eq!(res.headers()["access-control-allow-origin"], "*");
+
+    let _ = tx.send(());
+}
+
+#[test]
+fn layered_routers_keep_their_routes_and_typed_client() {
+    let layered = Layered::new(DemoRouter::new(), powered_by());
+    assert_eq!(milrouter::Router::routes(&layered), milrouter::Router::routes(&DemoRouter::new()));
+
+    let client = DemoRouter::client_with_transport("http://test".into(), Default::default(), TestServer::new(layered));
+    assert_eq!(common::runtime().block_on(client.add(2, 40)).unwrap(), 42);
+}
+
+#[test]
+fn layer_errors_answer_500() {
+    let failing = tower::layer::layer_fn(|_| {
+        tower::service_fn(|_: Request<_>| async { Err::<Response<Full<Bytes>>, _>("overloaded") })
+    });
+    let server = TestServer::new(Layered::new(DemoRouter::new(), failing));
+
+    let res = common::runtime().block_on(server.request(Request::put("/the_time").body(Bytes::new()).unwrap())).unwrap();
+    assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(res.text().unwrap(), "overloaded");
+}
+
+#[test]
+fn failing_layer_bodies_abort_the_response() {
+    let failing = tower::layer::layer_fn(|_| {
+        tower::service_fn(|_: Request<_>| async {
+            let chunks = vec![Ok(Frame::data(Bytes::from("partial"))), Err("backend went away")];
+            Ok::<_, &str>(Response::new(StreamBody::new(milrouter::futures::stream::iter(chunks))))
+        })
+    });
+    let server = TestServer::new(Layered::new(DemoRouter::new(), failing));
+
+    let res = common::runtime().block_on(server.request(Request::put("/the_time").body(Bytes::new()).unwrap()));
+    assert!(res.is_err(), "a failed body must not look like a complete response");
+}
+
+#[test]
+fn router_service_mounts_in_a_plain_hyper_server() {
+    let rt = common::runtime();
+    let addr: SocketAddr = rt.block_on(async {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let service = RouterService::new(DemoRouter::new());
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let service = TowerToHyperService::new(service.clone());
+                tokio::spawn(async move {
+                    let _ = auto::Builder::new(milrouter::hyper_util::rt::TokioExecutor::new())
+                        .serve_connection(TokioIo::new(stream), service)
+                        .await;
+                });
+            }
+        });
+        addr
+    });
+
+    let client = DemoRouter::client(format!("http://{addr}"), Default::default());
+    assert_eq!(rt.block_on(client.add(1, 2)).unwrap(), 3);
+}
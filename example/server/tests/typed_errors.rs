@@ -0,0 +1,44 @@
+mod common;
+
+use {
+    milrouter::{
+        ClientError,
+        bytes::Bytes,
+        hyper::{Request, StatusCode},
+        testing::TestServer,
+    },
+    server::{DefineError, DemoRouter},
+};
+
+#[test]
+fn error_variant_picks_the_status_and_body() {
+    let server = TestServer::new(DemoRouter::new());
+
+    let res = common::runtime().block_on(server.request(Request::get("/define/flux").body(Bytes::new()).unwrap())).unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    assert_eq!(res.json::<DefineError>().unwrap(), DefineError::Unknown { word: "flux".to_string() });
+
+    let res = common::runtime().block_on(server.request(Request::get("/define/admin").body(Bytes::new()).unwrap())).unwrap();
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+}
+
+#[test]
+fn client_returns_the_typed_error() {
+    let client = DemoRouter::client_with_transport("http://test".into(), Default::default(), TestServer::new(DemoRouter::new()));
+    let rt = common::runtime();
+
+    assert!(rt.block_on(client.define("router".to_string())).unwrap().starts_with("Something"));
+
+    match rt.block_on(client.define("flux".to_string())) {
+        Err(ClientError::Api(DefineError::Unknown { word })) => assert_eq!(word, "flux"),
+        other => panic!("Expected DefineError::Unknown, got {other:?}"),
+    }
+}
+
+#[test]
+fn other_failures_are_not_mistaken_for_endpoint_errors() {
+    let client = DemoRouter::client("http://127.0.0.1:1".into(), Default::default());
+
+    let res = common::runtime().block_on(client.define("router".to_string()));
+    assert!(matches!(res, Err(ClientError::Other(_))), "{res:?}");
+}
@@ -0,0 +1,37 @@
+use server::DemoRouter;
+
+#[test]
+fn schemas_become_types() {
+    let ts = milrouter::typescript::generate(&DemoRouter::openapi());
+
+    assert!(ts.contains("export interface SearchQuery {\n  haystack: string[];\n  needle: string;\n}"));
+    assert!(ts.contains("export interface Greeting {\n  name: string;\n  shout?: boolean;\n}"));
+    assert!(ts.contains("export type DefineError = \"Forbidden\" | { Unknown: { word: string } };"));
+}
+
+#[test]
+fn endpoints_become_functions() {
+    let ts = milrouter::typescript::generate(&DemoRouter::openapi());
+
+    assert!(ts.contains("export async function search(data: SearchQuery, options?: RequestOptions): Promise<SearchResult> {"));
+    assert!(ts.contains("send(\"POST\", `search`, data, options)"));
+
+    assert!(ts.contains("export async function theTime(options?: RequestOptions): Promise<string> {"));
+    assert!(ts.contains("send(\"PUT\", `the_time`, null, options)"));
+
+    assert!(ts.contains("export async function greet(data: Greeting, options?: RequestOptions): Promise<string> {"));
+    assert!(ts.contains("send(\"GET\", `greet`, data, options)"));
+}
+
+#[test]
+fn path_params_raw_bodies_and_errors() {
+    let ts = milrouter::typescript::generate(&DemoRouter::openapi());
+
+    assert!(ts.contains("export async function repeat(word: string, times: number, options?: RequestOptions): Promise<string> {"));
+    assert!(ts.contains("`repeat/${encodeURIComponent(String(word))}/${encodeURIComponent(String(times))}`"));
+
+    assert!(ts.contains("export async function versionBlob(options?: RequestOptions): Promise<Uint8Array> {"));
+    assert!(ts.contains("return readBody(res);"));
+
+    assert!(ts.contains("@throws {EndpointError<DefineError>} */\nexport async function define("));
+}
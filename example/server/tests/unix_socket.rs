@@ -0,0 +1,57 @@
+#![cfg(unix)]
+
+use server::{DemoRouter, SearchQuery};
+
+#[test]
+fn typed_client_roundtrips_over_unix_socket() {
+    let path = std::env::temp_dir().join(format!("milrouter-{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let listener = rt.block_on(async { tokio::net::UnixListener::bind(&path) }).unwrap();
+    let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+    let server = rt.spawn(milrouter::ServerBuilder::new(DemoRouter::new()).serve_listener_with_shutdown(listener, async move {
+        let _ = rx.await;
+    }));
+
+    let client = DemoRouter::client_unix(&path, Default::default()).unwrap();
+
+    let result = rt
+        .block_on(client.search(SearchQuery {
+            needle: "or".to_string(),
+            haystack: vec!["router".to_string(), "planet".to_string(), "orbit".to_string()],
+        }))
+        .unwrap();
+    assert_eq!(result.matches, vec!["orbit".to_string()]);
+
+    let raw = rt.block_on(client.version_blob()).unwrap();
+    assert_eq!(raw, b"milrouter-demo-v2\n".to_vec());
+
+    let _ = tx.send(());
+    rt.block_on(server).unwrap().unwrap();
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn socket_file_is_removed_after_shutdown() {
+    let path = std::env::temp_dir().join(format!("milrouter-restart-{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    for _ in 0..2 {
+        let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+        let server = rt.spawn(milrouter::ServerBuilder::new(DemoRouter::new()).serve_unix_with_shutdown(path.clone(), async move {
+            let _ = rx.await;
+        }));
+        while !path.exists() {
+            rt.block_on(async { tokio::time::sleep(std::time::Duration::from_millis(10)).await });
+        }
+
+        let client = DemoRouter::client_unix(&path, Default::default()).unwrap();
+        assert!(rt.block_on(client.the_time()).is_ok());
+
+        let _ = tx.send(());
+        rt.block_on(server).unwrap().unwrap();
+        assert!(!path.exists(), "Socket file left behind after shutdown");
+    }
+}
@@ -0,0 +1,140 @@
+use {
+    crate::{ApiError, ClientEndpoint, Endpoint, EndpointArgs, Method, TypedEndpoint},
+    futures::future::BoxFuture,
+    hyper::{HeaderMap, Request, Response, StatusCode, body::Bytes},
+    serde::Serialize,
+    std::fmt::{Debug, Display},
+};
+
+/// Carries requests for the typed `XxxClient` generated by `#[derive(Router)]`.
+///
+/// [`reqwest::Client`] is the default transport.  [`testing::TestServer`](crate::testing::TestServer)
+/// implements it too, so the same typed client can call a router in-process.
+pub trait ClientTransport: Send + Sync + 'static {
+    /// Send `req` (with an absolute URI) and return the raw response.
+    fn send(&self, req: Request<Bytes>) -> BoxFuture<'static, anyhow::Result<Response<Bytes>>>;
+}
+
+impl ClientTransport for reqwest::Client {
+    fn send(&self, req: Request<Bytes>) -> BoxFuture<'static, anyhow::Result<Response<Bytes>>> {
+        let (parts, body) = req.into_parts();
+        let sent = self.request(parts.method, parts.uri.to_string()).headers(parts.headers).body(body).send();
+
+        Box::pin(async move {
+            let resp = sent.await?;
+            let mut builder = Response::builder().status(resp.status()).version(resp.version());
+            if let Some(headers) = builder.headers_mut() {
+                headers.extend(resp.headers().clone());
+            }
+
+            Ok(builder.body(resp.bytes().await?)?)
+        })
+    }
+}
+
+/// `true` if the response says its body is gzip-compressed.
+pub fn is_gzipped(res: &Response<Bytes>) -> bool {
+    res.headers()
+        .get("content-encoding")
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.split(',').any(|encoding| encoding.trim().eq_ignore_ascii_case("gzip")))
+        .unwrap_or(false)
+}
+
+/// Send `req` through `transport`, returning the status and the body with any gzip encoding removed.
+/// Used by the generated client methods.
+pub async fn send_request(transport: &dyn ClientTransport, req: Request<Bytes>) -> anyhow::Result<(StatusCode, Bytes)> {
+    let res = transport.send(req).await?;
+    let gzipped = is_gzipped(&res);
+    let (parts, bytes) = res.into_parts();
+
+    let bytes = match gzipped {
+        true => {
+            let mut decompressed = Vec::new();
+            crate::gz_decompress(bytes.as_ref(), &mut decompressed)?;
+            Bytes::from(decompressed)
+        }
+        false => bytes,
+    };
+
+    Ok((parts.status, bytes))
+}
+
+/// Build the request for endpoint `E` from the client method's `args` and send it,
+/// returning the status and decompressed body.  Used by the generated client methods.
+///
+/// `default_path` is the router variant's name, used unless the endpoint declares a `path`.
+pub async fn send_endpoint<E>(
+    transport: &dyn ClientTransport,
+    host: &str,
+    headers: &HeaderMap,
+    default_path: &str,
+    args: <E as EndpointArgs<E::Client>>::Args,
+) -> anyhow::Result<(StatusCode, Bytes)>
+where
+    E: TypedEndpoint + ClientEndpoint<E::Client>,
+    <E as Endpoint<E::Client>>::Data: Serialize,
+{
+    let (path, data) = E::encode_args(args)?;
+    let url = format!("{}/{}", host.trim_end_matches('/'), path.as_deref().unwrap_or(default_path));
+
+    let mut req = match E::method() {
+        Method::GET => {
+            let query = crate::encode_query(&data)?;
+            let url = match query.is_empty() {
+                true => url,
+                false => format!("{url}?{query}"),
+            };
+
+            Request::get(url).body(Bytes::new())?
+        }
+        method => Request::builder()
+            .method(method)
+            .uri(&url)
+            .header("Content-Type", "application/json")
+            .body(Bytes::from(serde_json::to_vec(&data)?))?,
+    };
+    req.headers_mut().extend(headers.clone());
+
+    send_request(transport, req).await
+}
+
+/// The error for a non-success response that isn't an endpoint's own [`ApiError`].
+pub fn status_error(status: StatusCode, bytes: &[u8]) -> anyhow::Error {
+    anyhow::anyhow!("{status}: {}", String::from_utf8_lossy(bytes))
+}
+
+/// Error returned by typed client methods of endpoints declared as `Result<T, E>`.
+#[derive(Debug)]
+pub enum ClientError<E> {
+    /// The endpoint answered with one of its own errors.
+    Api(E),
+    /// The request failed for any other reason: transport, auth, bad input or an undecodable response.
+    Other(anyhow::Error),
+}
+
+impl<E: ApiError> ClientError<E> {
+    /// Decode a non-success response, falling back to [`ClientError::Other`]
+    /// if the body isn't one of the endpoint's errors.
+    pub fn from_response(status: StatusCode, bytes: Bytes) -> Self {
+        match serde_json::from_slice::<E>(&bytes) {
+            Ok(e) => ClientError::Api(e),
+            Err(_) => ClientError::Other(status_error(status, &bytes)),
+        }
+    }
+}
+
+impl<E> From<anyhow::Error> for ClientError<E> {
+    fn from(e: anyhow::Error) -> Self { ClientError::Other(e) }
+}
+
+impl<E: Debug> Display for ClientError<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ClientError::Api(e) => write!(f, "{e:?}"),
+            ClientError::Other(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl<E: Debug> std::error::Error for ClientError<E> {}
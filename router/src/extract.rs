@@ -0,0 +1,131 @@
+//! Reading endpoint input from requests.
+//!
+//! Input the client got wrong is answered with a JSON [`InputError`] rather
+//! than a panic, so a bad request can be told apart from a server bug:
+//!
+//! ```json
+//! {"status":400,"error":"invalid_body","message":"invalid type: integer `1`, expected a string","field":"haystack[0]"}
+//! ```
+
+use {
+    crate::{Body, MilBody},
+    http_body_util::{BodyExt, LengthLimitError, Limited},
+    hyper::{Request, Response, body::Incoming, header::CONTENT_TYPE},
+    serde::{Deserialize, Serialize, de::DeserializeOwned},
+    std::fmt::Display,
+};
+
+/// Largest request body an endpoint accepts unless [`ServerBuilder::max_body_size`](crate::ServerBuilder::max_body_size)
+/// says otherwise.
+pub const DEFAULT_MAX_BODY_SIZE: usize = 2 * 1024 * 1024;
+
+/// Request extension holding the body size limit configured on the server.
+#[derive(Debug, Clone, Copy)]
+pub struct BodyLimit(pub usize);
+
+/// What was wrong with a request's input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InputErrorKind {
+    /// The body couldn't be read or wasn't valid JSON for the endpoint's `Data` (400).
+    InvalidBody,
+    /// The query string didn't match a `GET` endpoint's `Data` (400).
+    InvalidQuery,
+    /// A path parameter couldn't be parsed into its argument's type (400).
+    InvalidPath,
+    /// The body was larger than the server's limit (413).
+    BodyTooLarge,
+    /// The body was sent with a `Content-Type` other than JSON (415).
+    UnsupportedMediaType,
+}
+
+/// Machine-readable error returned when a request's input can't be read.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InputError {
+    pub status: u16,
+    pub error: InputErrorKind,
+    pub message: String,
+    /// Path to the offending field, e.g. `haystack[0]`, when the input parsed but didn't fit.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+}
+
+impl InputError {
+    fn new(error: InputErrorKind, message: impl Display) -> Self {
+        let status = match error {
+            InputErrorKind::InvalidBody | InputErrorKind::InvalidQuery | InputErrorKind::InvalidPath => 400,
+            InputErrorKind::BodyTooLarge => 413,
+            InputErrorKind::UnsupportedMediaType => 415,
+        };
+
+        Self { status, error, message: message.to_string(), field: None }
+    }
+
+    fn from_path<E: Display>(error: InputErrorKind, err: serde_path_to_error::Error<E>) -> Self {
+        let field = err.path().to_string();
+        Self { field: (field != ".").then_some(field), ..Self::new(error, err.inner()) }
+    }
+
+    /// A path parameter that failed to parse.
+    pub fn path_param(name: &str, err: impl Display) -> Self {
+        Self { field: Some(name.to_string()), ..Self::new(InputErrorKind::InvalidPath, err) }
+    }
+
+    pub fn into_response(self) -> Response<MilBody> {
+        let body = serde_json::to_vec(&self).unwrap_or_default();
+        Response::builder()
+            .status(self.status)
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(body.as_slice()).boxed())
+            .unwrap()
+    }
+}
+
+impl Display for InputError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.field {
+            Some(field) => write!(f, "{} ({field}): {}", self.status, self.message),
+            None => write!(f, "{}: {}", self.status, self.message),
+        }
+    }
+}
+
+impl std::error::Error for InputError {}
+
+fn is_json(content_type: &str) -> bool {
+    let essence = content_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+    essence == "application/json" || (essence.starts_with("application/") && essence.ends_with("+json"))
+}
+
+/// Read and deserialize a JSON request body, within the request's [`BodyLimit`].
+///
+/// A missing `Content-Type` is accepted; anything other than JSON is not.
+pub async fn read_json<T: DeserializeOwned>(req: Request<Incoming>) -> Result<T, InputError> {
+    if let Some(content_type) = req.headers().get(CONTENT_TYPE) {
+        let content_type = content_type.to_str().unwrap_or_default();
+        if !is_json(content_type) {
+            return Err(InputError::new(
+                InputErrorKind::UnsupportedMediaType,
+                format!("Expected an application/json body, got {content_type}"),
+            ));
+        }
+    }
+
+    let limit = req.extensions().get::<BodyLimit>().map_or(DEFAULT_MAX_BODY_SIZE, |l| l.0);
+    let bytes = match Limited::new(req.into_body(), limit).collect().await {
+        Ok(body) => body.to_bytes(),
+        Err(e) if e.is::<LengthLimitError>() => {
+            return Err(InputError::new(InputErrorKind::BodyTooLarge, format!("Body exceeds the {limit} byte limit")));
+        }
+        Err(e) => return Err(InputError::new(InputErrorKind::InvalidBody, format!("Failed to read body: {e}"))),
+    };
+
+    serde_path_to_error::deserialize(&mut serde_json::Deserializer::from_slice(&bytes))
+        .map_err(|e| InputError::from_path(InputErrorKind::InvalidBody, e))
+}
+
+/// Deserialize a `GET` endpoint's input from the URL query string.
+pub fn read_query<T: DeserializeOwned>(query: &str) -> Result<T, InputError> {
+    serde_path_to_error::deserialize(serde_urlencoded::Deserializer::new(form_urlencoded::parse(query.as_bytes())))
+        .map_err(|e| InputError::from_path(InputErrorKind::InvalidQuery, e))
+}
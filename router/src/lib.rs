@@ -4,9 +4,12 @@ use {
     std::{
         fmt::Display,
         io::{Read, Write},
+        sync::Arc,
     },
 };
 
+mod path;
+
 #[cfg(any(target_arch = "wasm32", target_arch = "wasm64"))]
 pub mod wasm;
 
@@ -14,14 +17,77 @@ pub mod wasm;
 pub mod server;
 
 #[cfg(not(any(target_arch = "wasm32", target_arch = "wasm64")))]
-pub use server::*;
-pub use {anyhow, milrouter_macros::*, tokio};
+mod client;
+
+#[cfg(not(any(target_arch = "wasm32", target_arch = "wasm64")))]
+mod extract;
+
+#[cfg(not(any(target_arch = "wasm32", target_arch = "wasm64")))]
+mod mount;
+
+#[cfg(not(any(target_arch = "wasm32", target_arch = "wasm64")))]
+mod state;
+
+#[cfg(not(any(target_arch = "wasm32", target_arch = "wasm64")))]
+pub mod testing;
+
+#[cfg(all(feature = "tls", not(any(target_arch = "wasm32", target_arch = "wasm64"))))]
+pub mod tls;
+
+#[cfg(all(feature = "tower", not(any(target_arch = "wasm32", target_arch = "wasm64"))))]
+pub mod tower;
+
+#[cfg(feature = "openapi")]
+pub mod openapi;
+#[cfg(feature = "openapi")]
+pub mod typescript;
+
+/// Expands to its input only when the `openapi` feature is enabled.  Used by the generated code.
+///
+/// Input starting with `@required` is what a router's `#[openapi]` attribute asks for, so
+/// without the feature it becomes a compile error instead of vanishing.
+#[cfg(feature = "openapi")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __openapi {
+    (@required $($item:tt)*) => { $($item)* };
+    ($($item:tt)*) => { $($item)* };
+}
+
+#[cfg(not(feature = "openapi"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __openapi {
+    (@required $($item:tt)*) => {
+        compile_error!("#[openapi] requires milrouter's `openapi` feature");
+    };
+    ($($item:tt)*) => {};
+}
+
+/// The reverse of `__openapi!`: expands to its input only when the `openapi` feature is disabled.
+#[cfg(feature = "openapi")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __no_openapi {
+    ($($item:tt)*) => {};
+}
+
+#[cfg(not(feature = "openapi"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __no_openapi {
+    ($($item:tt)*) => { $($item)* };
+}
+
+#[cfg(not(any(target_arch = "wasm32", target_arch = "wasm64")))]
+pub use {client::*, extract::*, mount::*, server::*, state::*};
+pub use {anyhow, hyper::Method, milrouter_macros::*, path::*, serde, serde_urlencoded, tokio};
 #[cfg(not(any(target_arch = "wasm32", target_arch = "wasm64")))]
-pub use {bytes, futures, futures::future::BoxFuture, http_body_util, hyper, hyper_util, reqwest, serde, serde_json, tracing};
+pub use {bytes, futures, futures::future::BoxFuture, http_body_util, hyper, hyper_util, reqwest, serde_json, tracing};
 use {
     bytes::Bytes,
     http_body_util::Full,
-    hyper::{Request, Response},
+    hyper::{Request, Response, http::Extensions},
 };
 
 /// Endpoint attribute allowing all requests through, regardless of authentication headers.
@@ -39,11 +105,110 @@ pub trait Endpoint<C> {
     /// The return type of the endpoint.
     /// For streaming endpoints this will be [`ResponseStream`]; for raw endpoints [`Vec<u8>`].
     type Returns: Send;
+    /// The error type of the endpoint: [`anyhow::Error`], or an [`ApiError`] for `Result<T, E>` endpoints.
+    type Error: Send;
 
-    /// `true` for endpoints declared with `idempotent = true` (uses `PUT`).
+    /// `true` for endpoints declared with `idempotent = true` (uses `PUT`) or `method = get`.
     fn is_idempotent() -> bool;
+    /// The HTTP method clients use to call this endpoint.
+    fn method() -> Method {
+        match Self::is_idempotent() {
+            true => Method::PUT,
+            false => Method::POST,
+        }
+    }
+    /// Whether a request made with `method` should be routed to this endpoint:
+    /// only requests using the endpoint's own [`method`](Self::method) are.
+    fn accepts(method: &Method) -> bool { method == Self::method() }
     /// The URL path segment for this endpoint (snake_case function name).
     fn path() -> &'static str;
+    /// The pattern given with `#[endpoint(path = "...")]`, e.g. `/users/{id}/posts`.
+    /// `None` routes the endpoint by its router variant name instead.
+    fn route() -> Option<&'static str> { None }
+    /// Describe this endpoint for [`Router::routes`]. `default_path` is the router variant's
+    /// name, used unless the endpoint declares its own `path`.
+    ///
+    /// `#[endpoint]` fills in every field; hand-written impls get a JSON route with the
+    /// full type names and no auth function.
+    fn descriptor(default_path: &str) -> RouteDescriptor {
+        RouteDescriptor {
+            path: format!("/{}", Self::route().unwrap_or(default_path).trim_matches('/')),
+            method: Self::method(),
+            mode: RouteMode::Json,
+            input: std::any::type_name::<Self::Data>(),
+            output: std::any::type_name::<Self::Returns>(),
+            auth: "",
+        }
+    }
+}
+
+/// How the arguments of a client call become a request to an endpoint.
+///
+/// Implemented automatically by `#[endpoint]` for non-streaming endpoints, on every target.
+pub trait EndpointArgs<C>: Endpoint<C> {
+    /// The arguments of a client call: the endpoint's path parameters followed by its
+    /// body arguments (a tuple when there is more than one).
+    type Args;
+
+    /// The types of the same arguments as a tuple, `(T,)` for one.  `#[derive(Router)]` reads
+    /// it to give the client method one parameter per argument.
+    type Params;
+
+    /// Split client arguments into the request path (`None` for the default
+    /// route) and the request body.
+    fn encode_args(args: Self::Args) -> anyhow::Result<(Option<String>, <Self as Endpoint<C>>::Data)>;
+}
+
+/// How an endpoint's successful response body is produced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouteMode {
+    /// The return value, serialized as JSON.
+    Json,
+    /// `#[endpoint(raw)]` bytes, sent as-is.
+    Raw,
+    /// `#[endpoint(stream)]` chunks.
+    Stream,
+}
+
+/// One route served by a router, as listed by [`Router::routes`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteDescriptor {
+    /// The request path, e.g. `/search` or `/users/{id}/posts`.
+    pub path: String,
+    pub method: Method,
+    pub mode: RouteMode,
+    /// The endpoint's `Data` type as written in its signature, e.g. `SearchQuery`.
+    pub input: &'static str,
+    /// The endpoint's success type, e.g. `Vec<String>`.
+    pub output: &'static str,
+    /// The function named in `#[endpoint(auth = ...)]`.
+    pub auth: &'static str,
+}
+
+/// A typed error an endpoint can return as `Result<T, E>` instead of `anyhow::Result<T>`.
+///
+/// The error is sent to the client as JSON with the status picked by [`ApiError::status`],
+/// and the typed client hands it back as [`ClientError::Api`].
+///
+/// ```ignore
+/// #[derive(Debug, Serialize, Deserialize)]
+/// enum LookupError {
+///     NotFound { id: u64 },
+///     Forbidden,
+/// }
+///
+/// impl ApiError for LookupError {
+///     fn status(&self) -> u16 {
+///         match self {
+///             LookupError::NotFound { .. } => 404,
+///             LookupError::Forbidden => 403,
+///         }
+///     }
+/// }
+/// ```
+pub trait ApiError: Serialize + DeserializeOwned + std::fmt::Debug + Send + 'static {
+    /// The HTTP status code to answer with.
+    fn status(&self) -> u16;
 }
 
 /// Implemented automatically by `#[derive(Router)]` for router enums.
@@ -52,6 +217,13 @@ pub trait Endpoint<C> {
 /// - A `new()` constructor.
 /// - A `route()` associated function used by the server helpers.
 /// - A typed `client()` on non-wasm targets.
+/// - A `with_state(state)` constructor, for endpoints taking `&S` or [`State<S>`] arguments.
+///
+/// ```ignore
+/// for route in MyRouter::new().routes() {
+///     tracing::info!("{} {} ({:?}) -> {}", route.method, route.path, route.mode, route.output);
+/// }
+/// ```
 pub trait Router: Display + Sized + Send {
     /// Route an incoming request and return the response.
     fn route(
@@ -59,8 +231,16 @@ pub trait Router: Display + Sized + Send {
         req: hyper::Request<Incoming>,
     ) -> futures::future::BoxFuture<'static, Result<hyper::Response<MilBody>, std::convert::Infallible>>;
 
-    /// Return the list of middlewares attached to this router.
-    fn middleware(&self) -> Vec<Box<dyn Middleware>>;
+    /// The middleware attached to this router with `#[middleware(...)]`.
+    /// The value `new()` or `with_state()` returns creates the instances once and shares them
+    /// by every request it serves; a bare router enum value creates them for each request.
+    fn middleware(&self) -> Vec<Arc<dyn Middleware>>;
+
+    /// Every endpoint route this router serves, in declaration order.
+    /// Assets, the HTML page and the OpenAPI document are not included.
+    ///
+    /// `#[derive(Router)]` lists them all; hand-written routers list none unless they override this.
+    fn routes(&self) -> Vec<RouteDescriptor> { Vec::new() }
 }
 
 /// Bridges an endpoint struct back into the router enum that owns it.
@@ -70,6 +250,14 @@ pub trait IntoRouter<R: Router> {
     fn router(self) -> R;
 }
 
+/// Encode `data` as a URL query string for a `GET` endpoint. Unit encodes as an empty string.
+pub fn encode_query<T: Serialize>(data: &T) -> anyhow::Result<String> {
+    match serde_json::to_value(data)? {
+        serde_json::Value::Null => Ok(String::new()),
+        _ => Ok(serde_urlencoded::to_string(data)?),
+    }
+}
+
 /// Gzip-compress `inp` into `out`. Used internally for JSON payloads.
 pub fn gz_compress(mut inp: impl Read, out: &mut impl Write) -> anyhow::Result<()> {
     let mut out = flate2::write::GzEncoder::new(out, flate2::Compression::default());
@@ -89,63 +277,65 @@ pub fn gz_decompress(mut inp: impl Read, out: &mut impl Write) -> anyhow::Result
 /// Middleware that can intercept requests before they reach an endpoint
 /// and transform responses after the endpoint has run.
 ///
-/// Both methods have default no-op implementations, so you only need to
+/// Each middleware is created once per router and shared by every request,
+/// so keep long-lived state (rate limit counters, caches) behind interior
+/// mutability, and per-request state (a stashed header) in the `cx` passed
+/// to both hooks.
+///
+/// Every method has a default no-op implementation, so you only need to
 /// override the hook(s) you care about.
 ///
 /// # Example
 ///
 /// ```ignore
-/// struct CorsMiddleware {
-///     origin: Option<String>,
-/// }
+/// #[derive(Clone)]
+/// struct Origin(String);
+///
+/// struct CorsMiddleware;
 ///
 /// impl CorsMiddleware {
-///     fn new() -> Self { CorsMiddleware { origin: None } }
+///     fn new() -> Self { CorsMiddleware }
 /// }
 ///
 /// impl Middleware for CorsMiddleware {
 ///     fn before(
-///         &mut self,
+///         &self,
 ///         req: &Request<Incoming>,
+///         cx: &mut Extensions,
 ///     ) -> BoxFuture<'static, anyhow::Result<Option<Response<Full<Bytes>>>>> {
-///         self.origin = req.headers()
-///             .get("origin")
-///             .and_then(|v| v.to_str().ok())
-///             .map(|s| s.to_string());
-///
-///         if req.method() == hyper::Method::OPTIONS {
-///             let res = Response::builder()
-///                 .status(200)
-///                 .header("Access-Control-Allow-Origin", "*")
-///                 .body(Full::new(Bytes::new()))
-///                 .unwrap();
-///             Box::pin(async { Ok(Some(res)) })
-///         } else {
-///             Box::pin(async { Ok(None) })
+///         if let Some(origin) = req.headers().get("origin").and_then(|v| v.to_str().ok()) {
+///             cx.insert(Origin(origin.to_string()));
 ///         }
+///         Box::pin(async { Ok(None) })
 ///     }
 ///
 ///     fn after(
-///         &mut self,
+///         &self,
 ///         res: &mut Response<()>,
+///         cx: &mut Extensions,
 ///     ) -> BoxFuture<'static, anyhow::Result<()>> {
-///         res.headers_mut().insert(
-///             "Access-Control-Allow-Origin",
-///             hyper::header::HeaderValue::from_static("*"),
-///         );
+///         let origin = cx.get::<Origin>().map_or("*", |o| o.0.as_str());
+///         if let Ok(origin) = hyper::header::HeaderValue::from_str(origin) {
+///             res.headers_mut().insert("Access-Control-Allow-Origin", origin);
+///         }
 ///         Box::pin(async { Ok(()) })
 ///     }
 /// }
 /// ```
-pub trait Middleware: Send {
+pub trait Middleware: Send + Sync + 'static {
     /// Inspect or reject the incoming request before it reaches the endpoint.
     ///
     /// Return `Ok(Some(response))` to short-circuit the router (e.g. rate
     /// limiting, CORS preflight).  Return `Ok(None)` to let the request
     /// continue to the next middleware or the endpoint.
+    ///
+    /// `cx` belongs to this request alone and is handed to [`after`](Self::after).
+    /// Values inserted into it also reach the endpoint, which takes them as
+    /// an [`Extension<T>`](crate::Extension) argument.
     fn before(
-        &mut self,
+        &self,
         _req: &Request<Incoming>,
+        _cx: &mut Extensions,
     ) -> futures::future::BoxFuture<'static, anyhow::Result<Option<Response<Full<Bytes>>>>> {
         Box::pin(async { Ok(None) })
     }
@@ -157,11 +347,32 @@ pub trait Middleware: Send {
     /// it.  You can still read or modify headers and the status code.  Any
     /// changes are merged back into the real response before it is sent.
     ///
-    /// If you need request data here, clone it into `self` during [`before`].
+    /// If you need request data here, stash it in `cx` during [`before`](Self::before).
     fn after(
-        &mut self,
+        &self,
         _res: &mut Response<()>,
+        _cx: &mut Extensions,
     ) -> futures::future::BoxFuture<'static, anyhow::Result<()>> {
         Box::pin(async { Ok(()) })
     }
+
+    /// Wrap or replace the whole response, body included, once the
+    /// [`after`](Self::after) hooks of the middleware registered alongside this one have run.
+    ///
+    /// Router middleware and a matched endpoint's scoped (group and endpoint) middleware
+    /// run as two layers: the scoped `after` and `map_response` hooks all finish before
+    /// the router's `after` hooks start, so those see the body a scoped `map_response` produced.
+    ///
+    /// Use it for response signing, body-size metrics or content rewriting.
+    /// Streaming bodies arrive unread: wrap them to see each chunk as it is
+    /// sent, or collect them to work on the whole body.  JSON responses and
+    /// assets arrive gzip-compressed with `Content-Encoding: gzip`, so decompress
+    /// them (and fix the headers) before rewriting.  An error answers 500.
+    fn map_response(
+        &self,
+        res: Response<MilBody>,
+        _cx: &mut Extensions,
+    ) -> futures::future::BoxFuture<'static, anyhow::Result<Response<MilBody>>> {
+        Box::pin(async { Ok(res) })
+    }
 }
\ No newline at end of file
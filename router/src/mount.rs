@@ -0,0 +1,119 @@
+//! Serving a router under a path prefix, so it can share a port (or an axum or hyper application)
+//! with other services.
+//!
+//! ```ignore
+//! serve(addr, Mount::new("/api/v1", MyRouter::new())).await?;
+//!
+//! // Clients include the prefix in their host.
+//! let client = MyRouter::client("http://localhost:8080/api/v1".into(), headers);
+//! ```
+
+use {
+    crate::{Body, Middleware, MilBody, RouteDescriptor, Router},
+    futures::future::BoxFuture,
+    hyper::{Request, Response, Uri, body::Incoming},
+    std::{
+        fmt::{self, Display},
+        sync::Arc,
+    },
+};
+
+/// A router served under a path prefix such as `/api/v1`.
+///
+/// The prefix is stripped before the router matches the path, and requests outside it answer 404
+/// without reaching the router or its middleware.
+pub struct Mount<R> {
+    prefix: String,
+    router: R,
+}
+
+impl<R> Mount<R> {
+    /// Serve `router` under `prefix`.  Leading and trailing slashes are optional.
+    pub fn new(prefix: impl AsRef<str>, router: R) -> Self {
+        let prefix = prefix.as_ref().trim_matches('/');
+        let prefix = match prefix.is_empty() {
+            true => String::new(),
+            false => format!("/{prefix}"),
+        };
+        Self { prefix, router }
+    }
+
+    /// The prefix, with a leading slash and no trailing one (empty for the root).
+    pub fn prefix(&self) -> &str { &self.prefix }
+}
+
+/// Everything stripped from a request's path before it reached the router: the [`Mount`]
+/// prefix and any nesting prefixes, e.g. `/api/v1/admin`.  Found in the request's extensions.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MountPrefix(pub String);
+
+/// A router's `#[fallback]` handler, boxed.
+pub type FallbackHandler = Arc<dyn Fn(Request<Incoming>) -> BoxFuture<'static, anyhow::Result<Response<MilBody>>> + Send + Sync>;
+
+/// An enclosing router's `#[fallback]`, handed down in the request's extensions to the routers
+/// it nests.  A nested router without a fallback of its own answers its unmatched paths with it,
+/// after putting back the URI and [`MountPrefix`] the enclosing router saw.
+#[derive(Clone)]
+pub struct InheritedFallback {
+    pub uri: Uri,
+    pub prefix: MountPrefix,
+    pub handler: FallbackHandler,
+}
+
+impl InheritedFallback {
+    /// Run the fallback for `req` as the enclosing router would have.
+    pub fn call(&self, mut req: Request<Incoming>) -> BoxFuture<'static, anyhow::Result<Response<MilBody>>> {
+        *req.uri_mut() = self.uri.clone();
+        req.extensions_mut().insert(self.prefix.clone());
+        (self.handler)(req)
+    }
+}
+
+/// Remove `prefix` (e.g. `/admin`) from the start of the request's path, keeping the query,
+/// and add it to the request's [`MountPrefix`].
+/// Returns `false`, leaving the request as it was, if the path lies outside the prefix.
+/// Used by [`Mount`] and by the generated code for nested routers.
+pub fn strip_path_prefix<B>(req: &mut Request<B>, prefix: &str) -> bool {
+    let path = match req.uri().path().strip_prefix(prefix) {
+        Some("") => "/",
+        Some(rest) if rest.starts_with('/') => rest,
+        _ => return false,
+    };
+
+    let path_and_query = match req.uri().query() {
+        Some(query) => format!("{path}?{query}"),
+        None => path.to_string(),
+    };
+    let mut parts = req.uri().clone().into_parts();
+    parts.path_and_query = Some(path_and_query.parse().expect("a suffix of a valid path is a valid path"));
+    *req.uri_mut() = Uri::from_parts(parts).expect("only the path of a valid URI was changed");
+
+    let outer = req.extensions_mut().remove::<MountPrefix>().unwrap_or_default();
+    req.extensions_mut().insert(MountPrefix(format!("{}{prefix}", outer.0)));
+    true
+}
+
+impl<R: Display> Display for Mount<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { self.router.fmt(f) }
+}
+
+impl<R: Router> Router for Mount<R> {
+    fn route(&self, mut req: Request<Incoming>) -> BoxFuture<'static, Result<Response<MilBody>, std::convert::Infallible>> {
+        if !strip_path_prefix(&mut req, &self.prefix) {
+            tracing::info!("[?] 404 Not Found (outside {}) {}", self.prefix, req.uri().path());
+            return Box::pin(async { Ok(Response::builder().status(404).body(Body::default().boxed()).unwrap()) });
+        }
+
+        self.router.route(req)
+    }
+
+    fn middleware(&self) -> Vec<Arc<dyn Middleware>> { self.router.middleware() }
+
+    fn routes(&self) -> Vec<RouteDescriptor> {
+        let mut routes = self.router.routes();
+        for route in &mut routes {
+            route.path = format!("{}{}", self.prefix, route.path);
+        }
+        routes
+    }
+}
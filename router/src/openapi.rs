@@ -0,0 +1,154 @@
+//! OpenAPI 3.1 documents for routers, enabled by the `openapi` feature.
+//!
+//! With the feature on, `#[derive(Router)]` generates `MyRouter::openapi()`, and every
+//! endpoint's `Data`, `Returns` and error type must implement [`schemars::JsonSchema`].
+//! Add `#[openapi("/openapi.json")]` to the router to also serve the document.
+//!
+//! ```ignore
+//! std::fs::write("openapi.json", serde_json::to_string_pretty(&MyRouter::openapi())?)?;
+//! ```
+
+pub use schemars;
+use {
+    crate::Method,
+    schemars::{JsonSchema, SchemaGenerator, generate::SchemaSettings},
+    serde_json::{Map, Value, json},
+};
+
+/// Describes an endpoint in a router's OpenAPI document.
+/// Implemented automatically by `#[endpoint]` when the `openapi` feature is enabled.
+pub trait OpenApiEndpoint {
+    /// Add this endpoint to `doc`. `default_path` is the router variant's name,
+    /// used unless the endpoint declares its own `path`.
+    fn describe(doc: &mut OpenApi, default_path: &str);
+}
+
+/// What an endpoint answers with on success.
+pub enum Output {
+    /// JSON matching the schema.
+    Json(Value),
+    /// `#[endpoint(raw)]` bytes.
+    Raw,
+    /// `#[endpoint(stream)]` chunks.
+    Stream,
+}
+
+/// One endpoint, as seen by the document.
+pub struct Operation {
+    pub id: &'static str,
+    pub method: Method,
+    /// The route pattern, e.g. `users/{id}/posts`.
+    pub path: String,
+    /// Path parameters and their schemas, in pattern order.
+    pub params: Vec<(&'static str, Value)>,
+    /// The schema of `Data`, or `None` for unit.
+    pub input: Option<Value>,
+    pub output: Output,
+    /// The schema of the endpoint's `ApiError`, or `None` for `anyhow` endpoints.
+    pub error: Option<Value>,
+}
+
+/// An OpenAPI 3.1 document being assembled from a router's endpoints.
+pub struct OpenApi {
+    title: String,
+    version: String,
+    generator: SchemaGenerator,
+    paths: Map<String, Value>,
+    /// Prepended to operation paths while describing a nested router.
+    prefix: String,
+}
+
+impl OpenApi {
+    pub fn new(title: impl Into<String>, version: impl Into<String>) -> Self {
+        let settings = SchemaSettings::draft2020_12().with(|s| {
+            s.definitions_path = "/components/schemas".into();
+            s.meta_schema = None;
+        });
+
+        Self {
+            title: title.into(),
+            version: version.into(),
+            generator: settings.into_generator(),
+            paths: Map::new(),
+            prefix: String::new(),
+        }
+    }
+
+    /// The schema for `T`, registering any named types under `components/schemas`.
+    pub fn schema_for<T: ?Sized + JsonSchema>(&mut self) -> Value { self.generator.subschema_for::<T>().to_value() }
+
+    pub fn add(&mut self, op: Operation) {
+        let mut parameters = op
+            .params
+            .into_iter()
+            .map(|(name, schema)| json!({ "name": name, "in": "path", "required": true, "schema": schema }))
+            .collect::<Vec<_>>();
+
+        let mut operation = Map::new();
+        operation.insert("operationId".into(), json!(op.id));
+
+        match (op.input, op.method == Method::GET) {
+            (None, _) => {}
+            (Some(schema), true) => {
+                parameters.push(json!({ "name": "query", "in": "query", "style": "form", "explode": true, "schema": schema }))
+            }
+            (Some(schema), false) => {
+                operation.insert(
+                    "requestBody".into(),
+                    json!({ "required": true, "content": { "application/json": { "schema": schema } } }),
+                );
+            }
+        }
+
+        if !parameters.is_empty() {
+            operation.insert("parameters".into(), Value::Array(parameters));
+        }
+
+        let ok = match op.output {
+            Output::Json(schema) => json!({ "description": "OK", "content": { "application/json": { "schema": schema } } }),
+            Output::Raw => json!({ "description": "OK", "content": { "application/octet-stream": {} } }),
+            Output::Stream => json!({
+                "description": "Streamed response",
+                "content": { "application/octet-stream": {} },
+                "x-milrouter-stream": true,
+            }),
+        };
+        let mut responses = Map::new();
+        responses.insert("200".into(), ok);
+        match op.error {
+            Some(schema) => responses.insert(
+                "default".into(),
+                json!({ "description": "Endpoint error", "content": { "application/json": { "schema": schema } } }),
+            ),
+            None => responses.insert(
+                "400".into(),
+                json!({ "description": "Endpoint error", "content": { "text/plain": { "schema": { "type": "string" } } } }),
+            ),
+        };
+        operation.insert("responses".into(), Value::Object(responses));
+
+        let path = format!("{}/{}", self.prefix, op.path.trim_matches('/'));
+        let item = self.paths.entry(path).or_insert_with(|| Value::Object(Map::new()));
+        if let Value::Object(item) = item {
+            item.insert(op.method.as_str().to_ascii_lowercase(), Value::Object(operation));
+        }
+    }
+
+    /// Run `describe` with every operation it adds placed under `prefix`, for nested routers.
+    pub fn nested(&mut self, prefix: &str, describe: impl FnOnce(&mut Self)) {
+        let outer = self.prefix.clone();
+        self.prefix = format!("{outer}/{}", prefix.trim_matches('/'));
+        describe(self);
+        self.prefix = outer;
+    }
+
+    /// The finished document.
+    pub fn into_json(mut self) -> Value {
+        json!({
+            "openapi": "3.1.0",
+            "info": { "title": self.title, "version": self.version },
+            "paths": self.paths,
+            "components": { "schemas": self.generator.take_definitions(true) },
+        })
+    }
+}
@@ -0,0 +1,84 @@
+//! Matching and building endpoint paths declared with `#[endpoint(path = "...")]`.
+//!
+//! Patterns are `/`-separated, with `{name}` standing in for a whole segment:
+//!
+//! ```ignore
+//! let params = milrouter::match_route("/users/{id}/posts", "users/42/posts").unwrap();
+//! assert_eq!(params.get("id"), Some("42"));
+//! ```
+
+use {
+    percent_encoding::{AsciiSet, CONTROLS, percent_decode_str, utf8_percent_encode},
+    std::{fmt::Display, str::FromStr},
+};
+
+/// Characters escaped when a parameter value is written into a path segment.
+const SEGMENT: &AsciiSet =
+    &CONTROLS.add(b' ').add(b'"').add(b'#').add(b'%').add(b'/').add(b'<').add(b'>').add(b'?').add(b'`').add(b'{').add(b'}');
+
+/// Values captured by the `{name}` segments of an endpoint's path, in pattern order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PathParams(Vec<(String, String)>);
+
+impl PathParams {
+    /// The percent-decoded value captured for `name`.
+    pub fn get(&self, name: &str) -> Option<&str> { self.0.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str()) }
+
+    /// Parse the value captured for `name` into `T`.
+    pub fn parse<T>(&self, name: &str) -> anyhow::Result<T>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        let value = self.get(name).ok_or(anyhow::anyhow!("Missing path parameter `{name}`"))?;
+        value.parse::<T>().map_err(|e| anyhow::anyhow!("Invalid path parameter `{name}` ({value}): {e}"))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> { self.0.iter().map(|(k, v)| (k.as_str(), v.as_str())) }
+
+    pub fn is_empty(&self) -> bool { self.0.is_empty() }
+}
+
+fn segments(path: &str) -> impl Iterator<Item = &str> { path.trim_matches('/').split('/') }
+
+fn param_name(segment: &str) -> Option<&str> { segment.strip_prefix('{').and_then(|s| s.strip_suffix('}')) }
+
+/// Match a request path against `pattern`, capturing its `{name}` segments.
+///
+/// Leading and trailing slashes are ignored on both sides.
+pub fn match_route(pattern: &str, path: &str) -> Option<PathParams> {
+    let mut pattern = segments(pattern);
+    let mut path = segments(path);
+    let mut params = Vec::new();
+
+    loop {
+        match (pattern.next(), path.next()) {
+            (None, None) => return Some(PathParams(params)),
+            (Some(expected), Some(actual)) => match param_name(expected) {
+                Some(_) if actual.is_empty() => return None,
+                Some(name) => params.push((name.to_string(), percent_decode_str(actual).decode_utf8().ok()?.into_owned())),
+                None if expected == actual => {}
+                None => return None,
+            },
+            _ => return None,
+        }
+    }
+}
+
+/// Build a request path from `pattern`, substituting each `{name}` with its percent-encoded value.
+///
+/// The result has no leading slash.
+pub fn fill_route(pattern: &str, params: &[(&str, String)]) -> anyhow::Result<String> {
+    let segments = segments(pattern)
+        .map(|segment| match param_name(segment) {
+            None => Ok(segment.to_string()),
+            Some(name) => params
+                .iter()
+                .find(|(k, _)| *k == name)
+                .map(|(_, v)| utf8_percent_encode(v, SEGMENT).to_string())
+                .ok_or(anyhow::anyhow!("Missing path parameter `{name}` for {pattern}")),
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    Ok(segments.join("/"))
+}
@@ -1,20 +1,40 @@
 use {
-    crate::{Endpoint, Router},
-    futures::{Stream, StreamExt, future::BoxFuture},
+    crate::{ApiError, BodyLimit, DEFAULT_MAX_BODY_SIZE, Endpoint, EndpointArgs, InputError, Middleware, PathParams, Router, State},
+    futures::{FutureExt, Stream, StreamExt, future::BoxFuture},
     http_body_util::{BodyExt, Full, StreamBody, combinators::BoxBody},
     hyper::{
         HeaderMap,
+        Request,
+        StatusCode,
         body::{Bytes, Frame, Incoming},
+        service::service_fn,
+    },
+    hyper_util::{
+        rt::{TokioExecutor, TokioIo, TokioTimer},
+        server::{
+            conn::auto,
+            graceful::{GracefulShutdown, Watcher},
+        },
     },
-    hyper_util::rt::TokioIo,
     std::{
         future::Future,
         marker::PhantomData,
         net::SocketAddr,
+        ops::Deref,
         pin::Pin,
+        sync::{
+            Arc,
+            atomic::{AtomicBool, Ordering},
+        },
         task::{Context, Poll},
+        time::Duration,
+    },
+    tokio::{
+        io::{AsyncRead, AsyncWrite},
+        net::TcpListener,
+        sync::{OwnedSemaphorePermit, Semaphore},
+        task::JoinSet,
     },
-    tokio::net::TcpListener,
 };
 
 // ── types ──────────────────────────────────────────────────────────────────
@@ -38,16 +58,16 @@ pub type MilBody = BoxBody<Bytes, std::convert::Infallible>;
 
 // ── IOTypeNotSend (internal hyper helper) ──────────────────────────────────
 
-pub struct IOTypeNotSend {
+pub struct IOTypeNotSend<T = tokio::net::TcpStream> {
     _marker: PhantomData<*const ()>,
-    stream: TokioIo<tokio::net::TcpStream>,
+    stream: TokioIo<T>,
 }
 
-impl IOTypeNotSend {
-    pub fn new(stream: TokioIo<tokio::net::TcpStream>) -> Self { Self { _marker: PhantomData, stream } }
+impl<T> IOTypeNotSend<T> {
+    pub fn new(stream: TokioIo<T>) -> Self { Self { _marker: PhantomData, stream } }
 }
 
-impl hyper::rt::Write for IOTypeNotSend {
+impl<T: AsyncWrite + Unpin> hyper::rt::Write for IOTypeNotSend<T> {
     fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<Result<usize, std::io::Error>> {
         Pin::new(&mut self.stream).poll_write(cx, buf)
     }
@@ -61,7 +81,7 @@ impl hyper::rt::Write for IOTypeNotSend {
     }
 }
 
-impl hyper::rt::Read for IOTypeNotSend {
+impl<T: AsyncRead + Unpin> hyper::rt::Read for IOTypeNotSend<T> {
     fn poll_read(
         mut self: Pin<&mut Self>,
         cx: &mut Context<'_>,
@@ -71,6 +91,13 @@ impl hyper::rt::Read for IOTypeNotSend {
     }
 }
 
+/// Any byte stream a connection can be served over, e.g. a TCP stream or a TLS session on top of one.
+pub(crate) trait ConnectionIo: AsyncRead + AsyncWrite + Send + Unpin + 'static {}
+
+impl<T: AsyncRead + AsyncWrite + Send + Unpin + 'static> ConnectionIo for T {}
+
+pub(crate) type BoxIo = Box<dyn ConnectionIo>;
+
 // ── Body (simple single-chunk body) ───────────────────────────────────────
 
 #[derive(Default)]
@@ -108,6 +135,93 @@ pub type AsyncHandler<I, O> = Box<dyn Fn(I) -> BoxFuture<'static, O> + Send + 's
 
 pub type AsyncHandler3<I, I2, I3, O> = Box<dyn Fn(I, I2, I3) -> BoxFuture<'static, O> + Send + 'static>;
 
+// ── errors ─────────────────────────────────────────────────────────────────
+
+/// Why an endpoint handler didn't produce a value.
+#[derive(Debug)]
+pub enum HandlerError<E> {
+    /// Input the handler reads itself, such as a path parameter, was invalid.
+    Input(InputError),
+    /// The endpoint returned an error.
+    Endpoint(E),
+    /// The router wasn't set up the way the endpoint needs, e.g. it was served without the
+    /// state the endpoint takes, or no middleware inserted an extension it asks for.
+    /// Logged, and answered with an empty 500.
+    Internal(anyhow::Error),
+}
+
+pub type HandlerResult<T, E> = Result<T, HandlerError<E>>;
+
+/// Turns an endpoint's error into the response sent to the client.
+///
+/// `anyhow::Error` becomes a plain-text 400; an [`ApiError`] is sent as JSON with its own status.
+pub trait EndpointError: Send + 'static {
+    fn into_response(self) -> hyper::Response<MilBody>;
+}
+
+impl EndpointError for anyhow::Error {
+    fn into_response(self) -> hyper::Response<MilBody> {
+        hyper::Response::builder().status(400).body(Body::from(self.to_string()).boxed()).unwrap()
+    }
+}
+
+impl<E: ApiError> EndpointError for E {
+    fn into_response(self) -> hyper::Response<MilBody> {
+        match serde_json::to_vec(&self) {
+            Ok(body) => hyper::Response::builder()
+                .status(self.status())
+                .header(hyper::header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.as_slice()).boxed())
+                .unwrap(),
+            Err(e) => {
+                tracing::error!("Failed to serialize endpoint error {self:?}: {e}");
+                hyper::Response::builder().status(500).body(Body::default().boxed()).unwrap()
+            }
+        }
+    }
+}
+
+// ── RequestContext ─────────────────────────────────────────────────────────
+
+/// Per-request data handed to an endpoint handler alongside its body.
+pub struct RequestContext {
+    pub headers: HeaderMap,
+    /// Segments captured by the endpoint's `path` pattern.
+    pub params: PathParams,
+    /// The request's extensions, which carry the router's [`State`] and
+    /// whatever the request's middleware put in its context.
+    pub extensions: hyper::http::Extensions,
+}
+
+impl RequestContext {
+    /// The state the router was given with `with_state()`, or an error if it wasn't given a state of type `S`.
+    pub fn state<S: Send + Sync + 'static>(&self) -> anyhow::Result<State<S>> {
+        self.extensions.get::<State<S>>().cloned().ok_or_else(|| {
+            anyhow::anyhow!("Endpoint expects state of type `{}`; serve the router with `with_state()`", std::any::type_name::<S>())
+        })
+    }
+
+    /// The value of type `T` a middleware inserted into the request's context, or an error if none did.
+    pub fn extension<T: Clone + Send + Sync + 'static>(&self) -> anyhow::Result<Extension<T>> {
+        self.extensions.get::<T>().cloned().map(Extension).ok_or_else(|| {
+            anyhow::anyhow!("Endpoint expects an extension of type `{}`; no middleware inserted one", std::any::type_name::<T>())
+        })
+    }
+}
+
+/// A value computed by middleware for the endpoint: a parsed session, a request ID, a tenant.
+///
+/// A middleware's `before` inserts it into the request's context with `cx.insert(value)`,
+/// and an endpoint declares an `Extension<T>` argument to receive a copy.
+#[derive(Clone, Debug)]
+pub struct Extension<T>(pub T);
+
+impl<T> Deref for Extension<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target { &self.0 }
+}
+
 // ── ServerEndpoint ─────────────────────────────────────────────────────────
 
 /// Server wiring for a single endpoint. Implemented automatically by `#[endpoint]`.
@@ -115,12 +229,23 @@ pub type AsyncHandler3<I, I2, I3, O> = Box<dyn Fn(I, I2, I3) -> BoxFuture<'stati
 /// Provides the auth function, the request handler, and flags for raw/streaming modes.
 #[allow(clippy::type_complexity)]
 pub trait ServerEndpoint<C>: Endpoint<C> {
-    /// The auth function declared in `#[endpoint(auth = ...)]`.
+    /// The auth function declared in `#[endpoint(auth = ...)]`.  It sees only the request's
+    /// headers, not the values middleware put in the request's context.
     fn auth() -> AsyncHandler<HeaderMap, Result<C, anyhow::Error>>;
 
     /// Handler for normal (JSON) and raw endpoints.
     /// For streaming endpoints use [`stream_handler`] instead.
-    fn handler() -> AsyncHandler3<C, HeaderMap, <Self as Endpoint<C>>::Data, anyhow::Result<<Self as Endpoint<C>>::Returns>>;
+    fn handler() -> AsyncHandler3<
+        C,
+        RequestContext,
+        <Self as Endpoint<C>>::Data,
+        HandlerResult<<Self as Endpoint<C>>::Returns, <Self as Endpoint<C>>::Error>,
+    >;
+
+    /// The middleware declared with `#[endpoint(middleware(...))]`, run after the
+    /// router's own and its groups'.  Each call creates new instances: the router serving the
+    /// endpoint calls it once and shares them by every request it serves.
+    fn middleware() -> Vec<Arc<dyn Middleware>> { Vec::new() }
 
     /// Returns `true` for `#[endpoint(raw)]` endpoints whose response bytes
     /// are returned as-is rather than JSON-serialised.
@@ -128,7 +253,9 @@ pub trait ServerEndpoint<C>: Endpoint<C> {
 
     /// Returns the streaming handler for `#[endpoint(stream)]` endpoints,
     /// or `None` for non-streaming endpoints.
-    fn stream_handler() -> Option<AsyncHandler3<C, HeaderMap, <Self as Endpoint<C>>::Data, anyhow::Result<ResponseStream>>> {
+    fn stream_handler()
+    -> Option<AsyncHandler3<C, RequestContext, <Self as Endpoint<C>>::Data, HandlerResult<ResponseStream, <Self as Endpoint<C>>::Error>>>
+    {
         None
     }
 }
@@ -145,17 +272,36 @@ pub trait TypedEndpoint: Endpoint<Self::Client> {
 
 /// Implemented by non-streaming endpoint structs to support the typed `.client()` API.
 /// The macro generates this impl automatically.
-pub trait ClientEndpoint<C>: Endpoint<C> {
+pub trait ClientEndpoint<C>: EndpointArgs<C> {
+    /// The error returned by the generated client method: [`anyhow::Error`],
+    /// or [`ClientError`] for endpoints with an [`ApiError`].
+    type ClientError: From<anyhow::Error>;
+
     /// Decode the raw HTTP response bytes into `Self::Returns`.
     fn decode_response(bytes: Bytes) -> anyhow::Result<<Self as Endpoint<C>>::Returns>;
+
+    /// Decode a non-success response into the client error.
+    fn decode_error(status: StatusCode, bytes: Bytes) -> Self::ClientError;
 }
 
 // ── serve functions ────────────────────────────────────────────────────────
 
+/// How long the `serve*` helpers wait for in-flight connections to finish
+/// once shutdown has been requested, unless told otherwise.
+pub const DEFAULT_SHUTDOWN_GRACE: Duration = Duration::from_secs(30);
+
+/// How long a client gets to complete the TLS handshake, unless told otherwise.
+#[cfg(feature = "tls")]
+pub const DEFAULT_TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The smallest read buffer hyper accepts for HTTP/1 connections.
+const MIN_BUF_SIZE: usize = 8192;
+
 /// Start an HTTP/1 server on a **new single-thread** Tokio runtime using a `LocalSet`.
 ///
 /// This is the simplest way to host a router when you don't already have a
 /// Tokio runtime running.  Call from `main()` or any non-async context.
+/// Use [`ServerBuilder`] to tune timeouts and connection limits.
 ///
 /// ```ignore
 /// milrouter::serve_local("127.0.0.1:8080".parse().unwrap(), MyRouter).unwrap();
@@ -164,36 +310,39 @@ pub fn serve_local<R>(addr: SocketAddr, router: R) -> anyhow::Result<()>
 where
     R: Router + Send + Sync + 'static,
 {
-    use {
-        hyper::server::conn::http1,
-        hyper::service::service_fn,
-    };
-
-    let rt = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
-    let ls = tokio::task::LocalSet::new();
-
-    let listener = ls.block_on(&rt, TcpListener::bind(addr))?;
-    tracing::info!("Listening on http://{}", addr);
-
-    let router = std::sync::Arc::new(router);
+    ServerBuilder::new(router).serve_local(addr)
+}
 
-    loop {
-        let (stream, _) = ls.block_on(&rt, listener.accept())?;
-        let io = IOTypeNotSend::new(TokioIo::new(stream));
-        let router = router.clone();
-        let service = service_fn(move |req| router.route(req));
-        ls.spawn_local(async move {
-            if let Err(err) = http1::Builder::new().serve_connection(io, service).await {
-                tracing::warn!("Error serving connection: {:?}", err);
-            }
-        });
-    }
+/// Like [`serve_local`], but stops once `signal` resolves.
+///
+/// After the signal fires the listener is closed, open connections are asked
+/// to finish their current request, and the function returns once they have
+/// all closed or `grace` has elapsed, whichever comes first.  Connections still
+/// open after `grace` are dropped.
+///
+/// ```ignore
+/// let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+/// std::thread::spawn(move || milrouter::serve_local_with_shutdown(addr, MyRouter, async { let _ = rx.await; }, grace));
+/// // ...
+/// let _ = tx.send(());
+/// ```
+pub fn serve_local_with_shutdown<R>(
+    addr: SocketAddr,
+    router: R,
+    signal: impl Future<Output = ()>,
+    grace: Duration,
+) -> anyhow::Result<()>
+where
+    R: Router + Send + Sync + 'static,
+{
+    ServerBuilder::new(router).shutdown_grace(grace).serve_local_with_shutdown(addr, signal)
 }
 
 /// Start an HTTP/1 server **inside an existing Tokio runtime**.
 ///
 /// Await this from an `async` context (e.g. inside `#[tokio::main]`).  Each
 /// accepted connection is spawned as a normal `tokio::task`.
+/// Use [`ServerBuilder`] to tune timeouts and connection limits.
 ///
 /// ```ignore
 /// #[tokio::main]
@@ -205,27 +354,741 @@ pub async fn serve<R>(addr: SocketAddr, router: R) -> anyhow::Result<()>
 where
     R: Router + Send + Sync + 'static,
 {
-    use {
-        hyper::server::conn::http1,
-        hyper::service::service_fn,
+    ServerBuilder::new(router).serve(addr).await
+}
+
+/// Like [`serve`], but stops once `signal` resolves.
+///
+/// After the signal fires the listener is closed, open connections are asked
+/// to finish their current request, and the future resolves once they have
+/// all closed or `grace` has elapsed, whichever comes first.  Connections still
+/// open after `grace` are dropped.
+///
+/// ```ignore
+/// milrouter::serve_with_shutdown(addr, MyRouter, async { let _ = tokio::signal::ctrl_c().await; }, grace).await?;
+/// ```
+pub async fn serve_with_shutdown<R>(
+    addr: SocketAddr,
+    router: R,
+    signal: impl Future<Output = ()>,
+    grace: Duration,
+) -> anyhow::Result<()>
+where
+    R: Router + Send + Sync + 'static,
+{
+    ServerBuilder::new(router).shutdown_grace(grace).serve_with_shutdown(addr, signal).await
+}
+
+/// Start an HTTP/1 server on an already-bound [`Listener`] **inside an existing Tokio runtime**.
+///
+/// Useful when the socket comes from elsewhere, e.g. bound to port `0` in a test
+/// or inherited through systemd socket activation (see [`listen_fds`]).
+///
+/// ```ignore
+/// let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+/// let addr = listener.local_addr()?;
+/// tokio::spawn(milrouter::serve_listener(listener, MyRouter));
+/// ```
+pub async fn serve_listener<R>(listener: impl Listener, router: R) -> anyhow::Result<()>
+where
+    R: Router + Send + Sync + 'static,
+{
+    ServerBuilder::new(router).serve_listener(listener).await
+}
+
+/// Like [`serve_local`], but serves an already-bound standard library listener.
+///
+/// The listener is switched to non-blocking mode and registered with the new runtime.
+pub fn serve_local_listener<R>(listener: std::net::TcpListener, router: R) -> anyhow::Result<()>
+where
+    R: Router + Send + Sync + 'static,
+{
+    ServerBuilder::new(router).serve_local_listener(listener)
+}
+
+/// Start an HTTP/1 server on a Unix domain socket **inside an existing Tokio runtime**.
+///
+/// Handy for services that only talk to a local sidecar.  Pair it with the
+/// generated `client_unix` to call the router over the same socket.
+///
+/// ```ignore
+/// milrouter::serve_unix("/run/my-service.sock", MyRouter).await.unwrap();
+/// ```
+#[cfg(unix)]
+pub async fn serve_unix<R>(path: impl AsRef<std::path::Path>, router: R) -> anyhow::Result<()>
+where
+    R: Router + Send + Sync + 'static,
+{
+    ServerBuilder::new(router).serve_unix(path).await
+}
+
+// ── Listener ───────────────────────────────────────────────────────────────
+
+/// A bound socket that [`ServerBuilder`] can accept connections from.
+///
+/// Implemented for Tokio's TCP listener and, on Unix, its Unix domain socket listener.
+pub trait Listener: Send + 'static {
+    /// The stream produced for each accepted connection.
+    type Io: AsyncRead + AsyncWrite + Send + Unpin + 'static;
+    /// The address type of the listener and its peers.
+    type Addr: std::fmt::Debug;
+
+    /// Wait for the next incoming connection.
+    fn accept(&self) -> impl Future<Output = std::io::Result<(Self::Io, Self::Addr)>> + Send;
+
+    /// The address this listener is bound to.
+    fn local_addr(&self) -> std::io::Result<Self::Addr>;
+
+    /// The address to log once serving starts, reached over `scheme` (`http` or `https`).
+    fn describe(&self, scheme: &str) -> std::io::Result<String> { Ok(format!("{scheme}://{:?}", self.local_addr()?)) }
+}
+
+impl Listener for TcpListener {
+    type Addr = SocketAddr;
+    type Io = tokio::net::TcpStream;
+
+    fn accept(&self) -> impl Future<Output = std::io::Result<(Self::Io, Self::Addr)>> + Send { TcpListener::accept(self) }
+
+    fn local_addr(&self) -> std::io::Result<Self::Addr> { TcpListener::local_addr(self) }
+}
+
+#[cfg(unix)]
+impl Listener for tokio::net::UnixListener {
+    type Addr = tokio::net::unix::SocketAddr;
+    type Io = tokio::net::UnixStream;
+
+    fn accept(&self) -> impl Future<Output = std::io::Result<(Self::Io, Self::Addr)>> + Send {
+        tokio::net::UnixListener::accept(self)
+    }
+
+    fn local_addr(&self) -> std::io::Result<Self::Addr> { tokio::net::UnixListener::local_addr(self) }
+
+    fn describe(&self, _scheme: &str) -> std::io::Result<String> {
+        Ok(match self.local_addr()?.as_pathname() {
+            Some(path) => format!("unix:{}", path.display()),
+            None => "unix:(unnamed)".to_string(),
+        })
+    }
+}
+
+/// Take the sockets passed to this process by systemd socket activation.
+///
+/// Follows `sd_listen_fds(0)`: when `LISTEN_PID` names this process, returns the
+/// descriptors listed by `LISTEN_FDS` (starting at fd 3) marked close-on-exec, so
+/// child processes don't inherit them.  Returns an empty list otherwise.  Only the
+/// first call hands them out, so no descriptor is owned twice.  The environment is
+/// left alone; see [`unset_listen_environment`] to clear it.
+///
+/// ```ignore
+/// let fd = milrouter::listen_fds().into_iter().next().expect("not socket activated");
+/// milrouter::serve_local_listener(std::net::TcpListener::from(fd), MyRouter)?;
+/// ```
+#[cfg(unix)]
+pub fn listen_fds() -> Vec<std::os::fd::OwnedFd> { listen_fds_with_names().into_iter().map(|(_, fd)| fd).collect() }
+
+/// Like [`listen_fds`], with each socket's name from `LISTEN_FDNAMES`: the `FileDescriptorName=`
+/// of its socket unit, or `"unknown"` when systemd didn't pass one.
+///
+/// ```ignore
+/// let fds = milrouter::listen_fds_with_names();
+/// let (_, api) = fds.into_iter().find(|(name, _)| name == "api").expect("no `api` socket");
+/// ```
+#[cfg(unix)]
+pub fn listen_fds_with_names() -> Vec<(String, std::os::fd::OwnedFd)> {
+    use std::{
+        os::fd::{FromRawFd, RawFd},
+        sync::atomic::{AtomicBool, Ordering},
     };
 
-    let listener = TcpListener::bind(addr).await?;
-    tracing::info!("Listening on http://{}", addr);
+    const SD_LISTEN_FDS_START: RawFd = 3;
+    static TAKEN: AtomicBool = AtomicBool::new(false);
+
+    let for_us = std::env::var("LISTEN_PID").ok().and_then(|pid| pid.parse::<u32>().ok()) == Some(std::process::id());
+    let count = std::env::var("LISTEN_FDS").ok().and_then(|n| n.parse::<RawFd>().ok()).unwrap_or(0);
+    let names = std::env::var("LISTEN_FDNAMES").unwrap_or_default();
+
+    if !for_us || count <= 0 || TAKEN.swap(true, Ordering::SeqCst) {
+        return Vec::new();
+    }
+
+    // Names line up with descriptors by position, so an empty entry still takes its slot.
+    let mut names = names.split(':');
+    (SD_LISTEN_FDS_START..SD_LISTEN_FDS_START + count)
+        .filter_map(|fd| {
+            let name = match names.next() {
+                Some(name) if !name.is_empty() => name.to_string(),
+                _ => "unknown".to_string(),
+            };
+            // SAFETY: `fcntl` on a descriptor number only inspects or updates that descriptor's flags.
+            let flags = unsafe { libc::fcntl(fd, libc::F_GETFD) };
+            if flags < 0 || unsafe { libc::fcntl(fd, libc::F_SETFD, flags | libc::FD_CLOEXEC) } < 0 {
+                tracing::warn!("Ignoring socket-activated fd {fd} ({name}): {}", std::io::Error::last_os_error());
+                return None;
+            }
+
+            // SAFETY: systemd passes these descriptors open and owned by this process, and `TAKEN`
+            // guarantees they are only wrapped once.
+            Some((name, unsafe { std::os::fd::OwnedFd::from_raw_fd(fd) }))
+        })
+        .collect()
+}
+
+/// Remove `LISTEN_PID`, `LISTEN_FDS` and `LISTEN_FDNAMES` from the environment, as
+/// `sd_listen_fds(1)` does, so child processes don't see them.  Take the sockets with
+/// [`listen_fds`] first: afterwards there are none to take.
+///
+/// # Safety
+///
+/// Modifies the environment, so no other thread may read or write it at the same time (see
+/// [`std::env::remove_var`]).  Call it early in `main`, before starting threads or a runtime.
+#[cfg(unix)]
+pub unsafe fn unset_listen_environment() {
+    for var in ["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"] {
+        // SAFETY: the caller guarantees nothing else touches the environment meanwhile.
+        unsafe { std::env::remove_var(var) };
+    }
+}
+
+// ── ServerBuilder ──────────────────────────────────────────────────────────
+
+/// Configures the connection handling used to host a [`Router`].
+///
+/// Every setting is optional; anything left alone keeps hyper's default.
+///
+/// ```ignore
+/// milrouter::ServerBuilder::new(MyRouter::new())
+///     .header_read_timeout(Duration::from_secs(5))
+///     .max_connections(1024)
+///     .serve("127.0.0.1:8080".parse().unwrap())
+///     .await?;
+/// ```
+pub struct ServerBuilder<R> {
+    router: Arc<R>,
+    header_read_timeout: Option<Duration>,
+    keep_alive: Option<bool>,
+    max_buf_size: Option<usize>,
+    max_headers: Option<usize>,
+    max_connections: Option<usize>,
+    max_concurrent_streams: Option<u32>,
+    max_body_size: usize,
+    protocol: Protocol,
+    shutdown_grace: Duration,
+    #[cfg(feature = "tls")]
+    tls: Option<Arc<crate::tls::rustls::ServerConfig>>,
+    #[cfg(feature = "tls")]
+    tls_handshake_timeout: Duration,
+}
+
+/// Which HTTP versions a server built by [`ServerBuilder`] speaks.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Protocol {
+    /// HTTP/1.1 only.
+    #[default]
+    Http1,
+    /// HTTP/2 only.  Over plain TCP clients must use prior knowledge (h2c).
+    Http2,
+    /// Detect HTTP/1.1 or HTTP/2 on each connection from the HTTP/2 connection preface.
+    Auto,
+}
+
+impl<R> ServerBuilder<R>
+where
+    R: Router + Send + Sync + 'static,
+{
+    pub fn new(router: R) -> Self {
+        Self {
+            router: Arc::new(router),
+            header_read_timeout: None,
+            keep_alive: None,
+            max_buf_size: None,
+            max_headers: None,
+            max_connections: None,
+            max_concurrent_streams: None,
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+            protocol: Protocol::default(),
+            shutdown_grace: DEFAULT_SHUTDOWN_GRACE,
+            #[cfg(feature = "tls")]
+            tls: None,
+            #[cfg(feature = "tls")]
+            tls_handshake_timeout: DEFAULT_TLS_HANDSHAKE_TIMEOUT,
+        }
+    }
+
+    /// Close HTTP/1 connections that haven't sent a complete request head within `timeout`.
+    ///
+    /// This is the main defence against slowloris-style clients.  hyper's default is 30 seconds.
+    #[must_use]
+    pub fn header_read_timeout(mut self, timeout: Duration) -> Self {
+        self.header_read_timeout = Some(timeout);
+        self
+    }
+
+    /// Choose the HTTP version(s) to serve.  Defaults to [`Protocol::Http1`].
+    #[must_use]
+    pub fn protocol(mut self, protocol: Protocol) -> Self {
+        self.protocol = protocol;
+        self
+    }
+
+    /// Enable or disable HTTP/1 keep-alive.  Enabled by default.  Has no effect on HTTP/2.
+    #[must_use]
+    pub fn keep_alive(mut self, enabled: bool) -> Self {
+        self.keep_alive = Some(enabled);
+        self
+    }
+
+    /// Cap the per-connection read buffer, which bounds the size of an HTTP/1 request head.
+    ///
+    /// # Panics
+    ///
+    /// If `bytes` is below hyper's minimum of 8192.
+    #[must_use]
+    pub fn max_buf_size(mut self, bytes: usize) -> Self {
+        assert!(bytes >= MIN_BUF_SIZE, "max_buf_size must be at least {MIN_BUF_SIZE} bytes, got {bytes}");
+        self.max_buf_size = Some(bytes);
+        self
+    }
+
+    /// Reject HTTP/1 requests carrying more than `count` headers.  hyper's default is 100.
+    #[must_use]
+    pub fn max_headers(mut self, count: usize) -> Self {
+        self.max_headers = Some(count);
+        self
+    }
+
+    /// Limit how many requests a single HTTP/2 connection may multiplex at once.
+    #[must_use]
+    pub fn max_concurrent_streams(mut self, count: u32) -> Self {
+        self.max_concurrent_streams = Some(count);
+        self
+    }
+
+    /// Stop accepting new connections while `count` are already open.
+    ///
+    /// # Panics
+    ///
+    /// If `count` is zero, which would never accept a connection.
+    #[must_use]
+    pub fn max_connections(mut self, count: usize) -> Self {
+        assert!(count > 0, "max_connections must be at least 1");
+        self.max_connections = Some(count);
+        self
+    }
+
+    /// Answer requests whose body is larger than `bytes` with 413 Payload Too Large.
+    /// Defaults to [`DEFAULT_MAX_BODY_SIZE`].
+    #[must_use]
+    pub fn max_body_size(mut self, bytes: usize) -> Self {
+        self.max_body_size = bytes;
+        self
+    }
+
+    /// How long to wait for in-flight connections once shutdown is requested, before dropping
+    /// the ones still open.  Defaults to [`DEFAULT_SHUTDOWN_GRACE`].
+    #[must_use]
+    pub fn shutdown_grace(mut self, grace: Duration) -> Self {
+        self.shutdown_grace = grace;
+        self
+    }
+
+    /// Wrap the router in a tower layer, such as tower-http's `TimeoutLayer` or `CompressionLayer`.
+    ///
+    /// Each layer wraps the ones added before it, so the last layer sees requests first.
+    /// Layers run before the router's own middleware.  See [`tower`](crate::tower).
+    #[cfg(feature = "tower")]
+    #[must_use]
+    pub fn layer<L>(self, layer: L) -> ServerBuilder<crate::tower::Layered<R, L::Service>>
+    where
+        L: crate::tower::Layer<crate::tower::RouterService<R>>,
+        crate::tower::Layered<R, L::Service>: Router + Sync + 'static,
+    {
+        ServerBuilder {
+            router: Arc::new(crate::tower::Layered::from_shared(self.router, layer)),
+            header_read_timeout: self.header_read_timeout,
+            keep_alive: self.keep_alive,
+            max_buf_size: self.max_buf_size,
+            max_headers: self.max_headers,
+            max_connections: self.max_connections,
+            max_concurrent_streams: self.max_concurrent_streams,
+            max_body_size: self.max_body_size,
+            protocol: self.protocol,
+            shutdown_grace: self.shutdown_grace,
+            #[cfg(feature = "tls")]
+            tls: self.tls,
+            #[cfg(feature = "tls")]
+            tls_handshake_timeout: self.tls_handshake_timeout,
+        }
+    }
+
+    /// Terminate TLS on every accepted connection using `config`.
+    ///
+    /// If the config doesn't list any ALPN protocols, ones matching [`Self::protocol`] are advertised.
+    #[cfg(feature = "tls")]
+    #[must_use]
+    pub fn tls(mut self, config: crate::tls::rustls::ServerConfig) -> Self {
+        self.tls = Some(Arc::new(config));
+        self
+    }
+
+    /// Drop connections that haven't completed the TLS handshake within `timeout`.
+    /// Defaults to [`DEFAULT_TLS_HANDSHAKE_TIMEOUT`].
+    ///
+    /// Until the handshake completes a connection holds one of the [`Self::max_connections`]
+    /// slots, and [`Self::header_read_timeout`] has not started counting.
+    #[cfg(feature = "tls")]
+    #[must_use]
+    pub fn tls_handshake_timeout(mut self, timeout: Duration) -> Self {
+        self.tls_handshake_timeout = timeout;
+        self
+    }
+
+    /// Terminate TLS using a PEM certificate chain and private key read from disk.
+    /// See [`tls::server_config_from_pem`](crate::tls::server_config_from_pem).
+    #[cfg(feature = "tls")]
+    pub fn tls_pem(self, cert: impl AsRef<std::path::Path>, key: impl AsRef<std::path::Path>) -> anyhow::Result<Self> {
+        Ok(self.tls(crate::tls::server_config_from_pem(cert, key)?))
+    }
+
+    /// Serve on a new single-thread runtime.  See [`serve_local`].
+    pub fn serve_local(self, addr: SocketAddr) -> anyhow::Result<()> {
+        self.serve_local_with_shutdown(addr, std::future::pending::<()>())
+    }
+
+    /// Serve on a new single-thread runtime until `signal` resolves.  See [`serve_local_with_shutdown`].
+    pub fn serve_local_with_shutdown(self, addr: SocketAddr, signal: impl Future<Output = ()>) -> anyhow::Result<()> {
+        self.serve_local_listener_with_shutdown(std::net::TcpListener::bind(addr)?, signal)
+    }
 
-    let router = std::sync::Arc::new(router);
+    /// Serve an already-bound listener on a new single-thread runtime.  See [`serve_local_listener`].
+    pub fn serve_local_listener(self, listener: std::net::TcpListener) -> anyhow::Result<()> {
+        self.serve_local_listener_with_shutdown(listener, std::future::pending::<()>())
+    }
+
+    /// Serve an already-bound listener on a new single-thread runtime until `signal` resolves.
+    pub fn serve_local_listener_with_shutdown(
+        self,
+        listener: std::net::TcpListener,
+        signal: impl Future<Output = ()>,
+    ) -> anyhow::Result<()> {
+        let rt = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+        let ls = tokio::task::LocalSet::new();
+
+        listener.set_nonblocking(true)?;
+        ls.block_on(&rt, async move {
+            let listener = TcpListener::from_std(listener)?;
+            self.accept_loop(listener, signal, LocalExec, |connections, conn| {
+                connections.spawn_local(async move {
+                    let Accepted { handshake, router, http, watcher, permit, body_limit } = conn;
+                    let io = match handshake.await {
+                        Ok(io) => IOTypeNotSend::new(TokioIo::new(io)),
+                        Err(err) => return tracing::warn!("Error during handshake: {:?}", err),
+                    };
+                    let service = service_fn(move |mut req: Request<Incoming>| {
+                        req.extensions_mut().insert(body_limit);
+                        router.route(req).map(|res| res.map(served))
+                    });
+                    if let Err(err) = watcher.watch(http.serve_connection(io, service)).await {
+                        tracing::warn!("Error serving connection: {:?}", err);
+                    }
+                    drop(permit);
+                });
+            })
+            .await
+        })
+    }
+
+    /// Serve inside the current Tokio runtime.  See [`serve`].
+    pub async fn serve(self, addr: SocketAddr) -> anyhow::Result<()> {
+        self.serve_with_shutdown(addr, std::future::pending::<()>()).await
+    }
+
+    /// Serve inside the current Tokio runtime until `signal` resolves.  See [`serve_with_shutdown`].
+    pub async fn serve_with_shutdown(self, addr: SocketAddr, signal: impl Future<Output = ()>) -> anyhow::Result<()> {
+        self.serve_listener_with_shutdown(TcpListener::bind(addr).await?, signal).await
+    }
+
+    /// Serve on a Unix domain socket bound at `path` inside the current Tokio runtime.
+    ///
+    /// The socket file must not already exist.  It is removed again once the server has shut down.
+    #[cfg(unix)]
+    pub async fn serve_unix(self, path: impl AsRef<std::path::Path>) -> anyhow::Result<()> {
+        self.serve_unix_with_shutdown(path, std::future::pending::<()>()).await
+    }
+
+    /// Like [`Self::serve_unix`], but stops once `signal` resolves.
+    #[cfg(unix)]
+    pub async fn serve_unix_with_shutdown(
+        self,
+        path: impl AsRef<std::path::Path>,
+        signal: impl Future<Output = ()>,
+    ) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let served = self.serve_listener_with_shutdown(tokio::net::UnixListener::bind(path)?, signal).await;
+        if let Err(e) = std::fs::remove_file(path) {
+            tracing::warn!("Failed to remove socket {}: {e}", path.display());
+        }
+        served
+    }
+
+    /// Serve connections from an already-bound [`Listener`] inside the current Tokio runtime.  See [`serve_listener`].
+    pub async fn serve_listener(self, listener: impl Listener) -> anyhow::Result<()> {
+        self.serve_listener_with_shutdown(listener, std::future::pending::<()>()).await
+    }
+
+    /// Serve connections from an already-bound [`Listener`] inside the current Tokio runtime until `signal` resolves.
+    pub async fn serve_listener_with_shutdown<L: Listener>(
+        self,
+        listener: L,
+        signal: impl Future<Output = ()>,
+    ) -> anyhow::Result<()> {
+        self.accept_loop(listener, signal, TokioExecutor::new(), |connections, conn| {
+            connections.spawn(async move {
+                let Accepted { handshake, router, http, watcher, permit, body_limit } = conn;
+                let io = match handshake.await {
+                    Ok(io) => TokioIo::new(io),
+                    Err(err) => return tracing::warn!("Error during handshake: {:?}", err),
+                };
+                let service = service_fn(move |mut req: Request<Incoming>| {
+                    req.extensions_mut().insert(body_limit);
+                    router.route(req).map(|res| res.map(served))
+                });
+                if let Err(err) = watcher.watch(http.serve_connection(io, service)).await {
+                    tracing::warn!("Error serving connection: {:?}", err);
+                }
+                drop(permit);
+            });
+        })
+        .await
+    }
 
-    loop {
-        let (stream, _) = listener.accept().await?;
-        let io = TokioIo::new(stream);
-        let router = router.clone();
-        let service = service_fn(move |req| router.route(req));
-        tokio::spawn(async move {
-            if let Err(err) = http1::Builder::new().serve_connection(io, service).await {
-                tracing::warn!("Error serving connection: {:?}", err);
+    fn http<E: Clone>(&self, exec: E) -> auto::Builder<E> {
+        let mut http = auto::Builder::new(exec);
+        http = match self.protocol {
+            Protocol::Http1 => http.http1_only(),
+            Protocol::Http2 => http.http2_only(),
+            Protocol::Auto => http,
+        };
+
+        let mut http1 = http.http1();
+        http1.timer(TokioTimer::new());
+        if let Some(timeout) = self.header_read_timeout {
+            http1.header_read_timeout(timeout);
+        }
+        if let Some(enabled) = self.keep_alive {
+            http1.keep_alive(enabled);
+        }
+        if let Some(bytes) = self.max_buf_size {
+            http1.max_buf_size(bytes);
+        }
+        if let Some(count) = self.max_headers {
+            http1.max_headers(count);
+        }
+
+        let mut http2 = http.http2();
+        http2.timer(TokioTimer::new());
+        if let Some(count) = self.max_concurrent_streams {
+            http2.max_concurrent_streams(count);
+        }
+
+        http
+    }
+
+    /// Accept connections until `signal` fires, handing each one to `spawn` to start its task in
+    /// `connections`, then drain.
+    async fn accept_loop<L, E, F>(
+        self,
+        listener: L,
+        signal: impl Future<Output = ()>,
+        exec: E,
+        spawn: F,
+    ) -> anyhow::Result<()>
+    where
+        L: Listener,
+        E: Clone,
+        F: Fn(&mut JoinSet<()>, Accepted<R, E>),
+    {
+        // Flipped once `signal` fires, so connections still mid-handshake give up.
+        let (stop, stopped) = tokio::sync::watch::channel(false);
+        let handshake = Handshake::new(&self, stopped);
+        tracing::info!("Listening on {}", listener.describe(handshake.scheme())?);
+
+        let http = self.http(exec);
+        let limit = self.max_connections.map(|n| Arc::new(Semaphore::new(n)));
+        let graceful = GracefulShutdown::new();
+        let mut connections = JoinSet::new();
+        let mut signal = std::pin::pin!(signal);
+
+        loop {
+            let permit = match &limit {
+                Some(limit) => tokio::select! {
+                    permit = limit.clone().acquire_owned() => Some(permit?),
+                    _ = &mut signal => break,
+                },
+                None => None,
+            };
+
+            let stream = tokio::select! {
+                conn = listener.accept() => conn?.0,
+                _ = &mut signal => break,
+            };
+
+            while connections.try_join_next().is_some() {}
+            spawn(&mut connections, Accepted {
+                handshake: handshake.accept(Box::new(stream)),
+                router: self.router.clone(),
+                http: http.clone(),
+                watcher: graceful.watcher(),
+                permit,
+                body_limit: BodyLimit(self.max_body_size),
+            });
+        }
+
+        drop(listener);
+        let _ = stop.send(true);
+        drain(graceful, connections, self.shutdown_grace).await;
+        Ok(())
+    }
+}
+
+/// Everything the task spawned for a single accepted connection needs.
+struct Accepted<R, E> {
+    /// Resolves to the stream to speak HTTP over, once any TLS handshake has completed.
+    handshake: BoxFuture<'static, std::io::Result<BoxIo>>,
+    router: Arc<R>,
+    http: auto::Builder<E>,
+    watcher: Watcher,
+    permit: Option<OwnedSemaphorePermit>,
+    body_limit: BodyLimit,
+}
+
+/// Upgrades freshly accepted streams before HTTP is served over them.
+#[derive(Clone)]
+struct Handshake {
+    #[cfg(feature = "tls")]
+    tls: Option<tokio_rustls::TlsAcceptor>,
+    #[cfg(feature = "tls")]
+    timeout: Duration,
+    #[cfg(feature = "tls")]
+    stopped: tokio::sync::watch::Receiver<bool>,
+}
+
+impl Handshake {
+    fn new<R>(builder: &ServerBuilder<R>, stopped: tokio::sync::watch::Receiver<bool>) -> Self {
+        #[cfg(not(feature = "tls"))]
+        let _ = (builder, stopped);
+
+        Self {
+            #[cfg(feature = "tls")]
+            tls: builder.tls.clone().map(|config| crate::tls::acceptor(config, builder.protocol)),
+            #[cfg(feature = "tls")]
+            timeout: builder.tls_handshake_timeout,
+            #[cfg(feature = "tls")]
+            stopped,
+        }
+    }
+
+    fn scheme(&self) -> &'static str {
+        #[cfg(feature = "tls")]
+        if self.tls.is_some() {
+            return "https";
+        }
+
+        "http"
+    }
+
+    fn accept(&self, io: BoxIo) -> BoxFuture<'static, std::io::Result<BoxIo>> {
+        #[cfg(feature = "tls")]
+        if let Some(tls) = self.tls.clone() {
+            let (timeout, mut stopped) = (self.timeout, self.stopped.clone());
+            return Box::pin(async move {
+                use std::io::{Error, ErrorKind};
+
+                tokio::select! {
+                    stream = tokio::time::timeout(timeout, tls.accept(io)) => match stream {
+                        Ok(stream) => Ok(Box::new(stream?) as BoxIo),
+                        Err(_) => Err(Error::new(ErrorKind::TimedOut, "TLS handshake timed out")),
+                    },
+                    _ = stopped.wait_for(|stopped| *stopped) => {
+                        Err(Error::new(ErrorKind::ConnectionAborted, "Server shut down during the TLS handshake"))
+                    }
+                }
+            });
+        }
+
+        Box::pin(async move { Ok(io) })
+    }
+}
+
+/// Spawns HTTP/2 stream tasks onto the current `LocalSet` for [`ServerBuilder::serve_local`].
+#[derive(Clone, Copy)]
+struct LocalExec;
+
+impl<F> hyper::rt::Executor<F> for LocalExec
+where
+    F: Future + 'static,
+    F::Output: 'static,
+{
+    fn execute(&self, fut: F) { tokio::task::spawn_local(fut); }
+}
+
+/// Wait for every watched connection to close, aborting the tasks of any still open after `grace`.
+async fn drain(graceful: GracefulShutdown, mut connections: JoinSet<()>, grace: Duration) {
+    tracing::info!("Shutting down, waiting for {} connection(s)", graceful.count());
+    tokio::select! {
+        _ = graceful.shutdown() => tracing::info!("All connections closed"),
+        _ = tokio::time::sleep(grace) => tracing::warn!("Timed out waiting for connections to close, aborting them"),
+    }
+    connections.shutdown().await;
+}
+
+// ── aborting response bodies ──────────────────────────────────────────────
+
+/// Put in a response's extensions by whoever produced a body that can fail part-way, such as a
+/// tower layer's.  Once aborted, the server drops the connection where the body ends instead of
+/// finishing the response cleanly, so the client can tell it was cut short.
+#[derive(Clone, Default)]
+pub(crate) struct BodyAbort(Arc<AtomicBool>);
+
+impl BodyAbort {
+    #[cfg(feature = "tower")]
+    pub(crate) fn abort(&self) { self.0.store(true, Ordering::Release) }
+
+    fn is_aborted(&self) -> bool { self.0.load(Ordering::Acquire) }
+}
+
+/// The body hyper sends for a router's response: the [`MilBody`], failing at its end if its
+/// [`BodyAbort`] was set.
+pub(crate) struct ServedBody {
+    body: MilBody,
+    abort: Option<BodyAbort>,
+}
+
+impl hyper::body::Body for ServedBody {
+    type Data = Bytes;
+    type Error = std::io::Error;
+
+    fn poll_frame(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Frame<Bytes>, std::io::Error>>> {
+        match Pin::new(&mut self.body).poll_frame(cx) {
+            Poll::Ready(None) if self.abort.as_ref().is_some_and(BodyAbort::is_aborted) => {
+                Poll::Ready(Some(Err(std::io::Error::other("response body failed part-way"))))
             }
-        });
+            Poll::Ready(Some(Ok(frame))) => Poll::Ready(Some(Ok(frame))),
+            Poll::Ready(Some(Err(never))) => match never {},
+            Poll::Ready(None) => Poll::Ready(None),
+            Poll::Pending => Poll::Pending,
+        }
     }
+
+    // A body that can still abort has to be polled to its end, even if it says it's done.
+    fn is_end_stream(&self) -> bool { self.abort.is_none() && self.body.is_end_stream() }
+
+    fn size_hint(&self) -> hyper::body::SizeHint { self.body.size_hint() }
+}
+
+/// Prepare a router's response for hyper.  Used wherever the crate serves a connection.
+pub(crate) fn served(response: hyper::Response<MilBody>) -> hyper::Response<ServedBody> {
+    let abort = response.extensions().get::<BodyAbort>().cloned();
+    response.map(|body| ServedBody { body, abort })
 }
 
 // ── helper: build a streaming MilBody from a ResponseStream ───────────────
@@ -234,5 +1097,59 @@ pub fn stream_to_body(stream: ResponseStream) -> MilBody {
     BodyExt::boxed(StreamBody::new(stream.map(|chunk| Ok(Frame::data(chunk)))))
 }
 
+// ── helpers: run middleware hooks (used by the generated router) ──────────
+
+/// Run each middleware's `before` hook in order.  The first response, or the first
+/// error as a 500, short-circuits the rest.
+pub async fn run_before(
+    middleware: &[Arc<dyn Middleware>],
+    req: &Request<Incoming>,
+    cx: &mut hyper::http::Extensions,
+) -> Option<hyper::Response<MilBody>> {
+    for mw in middleware {
+        match mw.before(req, cx).await {
+            Ok(Some(response)) => return Some(response.map(BodyExt::boxed)),
+            Ok(None) => {}
+            Err(e) => {
+                tracing::error!("[-] 500 Middleware before hook error: {}", e);
+                return Some(hyper::Response::builder().status(500).body(Body::from(e.to_string()).boxed()).unwrap());
+            }
+        }
+    }
+    None
+}
+
+/// Run each middleware's `after` hook in order on the response's head, then
+/// each `map_response` hook in the same order on the whole response.
+///
+/// The generated router calls this once for a matched endpoint's scoped middleware,
+/// then again for its own, so the scoped `map_response` hooks run before the router's `after`.
+pub async fn run_after(
+    middleware: &[Arc<dyn Middleware>],
+    response: hyper::Response<MilBody>,
+    cx: &mut hyper::http::Extensions,
+) -> hyper::Response<MilBody> {
+    let (parts, body) = response.into_parts();
+    let mut hollow = hyper::Response::from_parts(parts, ());
+    for mw in middleware {
+        if let Err(e) = mw.after(&mut hollow, cx).await {
+            tracing::error!("Middleware after hook error: {}", e);
+        }
+    }
+    let (parts, _) = hollow.into_parts();
+
+    let mut response = hyper::Response::from_parts(parts, body);
+    for mw in middleware {
+        response = match mw.map_response(response, cx).await {
+            Ok(response) => response,
+            Err(e) => {
+                tracing::error!("[-] 500 Middleware map_response hook error: {}", e);
+                return hyper::Response::builder().status(500).body(Body::from(e.to_string()).boxed()).unwrap();
+            }
+        };
+    }
+    response
+}
+
 // ── helper: re-export fmt for generated Display impls ─────────────────────
 pub use std::fmt as _fmt;
@@ -0,0 +1,84 @@
+//! Shared application state, handed to endpoints that ask for it.
+//!
+//! ```ignore
+//! struct AppState { pool: DbPool }
+//!
+//! #[endpoint(auth = all_aboard)]
+//! fn lookup(state: &AppState, id: u64) -> anyhow::Result<User> { state.pool.user(id) }
+//!
+//! serve(addr, MyRouter::with_state(AppState { pool })).await?;
+//! ```
+
+use {
+    crate::{Middleware, MilBody, Router},
+    futures::future::BoxFuture,
+    hyper::{Request, Response, body::Incoming},
+    std::{
+        fmt::{self, Display},
+        ops::Deref,
+        sync::Arc,
+    },
+};
+
+/// A router's shared state.  Declare a `State<S>` argument to take a handle to it,
+/// or `&S` to borrow it for the duration of the call.  Cheap to clone.
+pub struct State<S>(pub Arc<S>);
+
+impl<S> Clone for State<S> {
+    fn clone(&self) -> Self { Self(self.0.clone()) }
+}
+
+impl<S> Deref for State<S> {
+    type Target = S;
+
+    fn deref(&self) -> &Self::Target { &self.0 }
+}
+
+/// The middleware instances of a `#[derive(Router)]` router: its own, its groups', its endpoints'
+/// and those of the routers it nests.  Implemented automatically by `#[derive(Router)]`.
+pub trait RouterMiddleware {
+    type Instances: Send + Sync + 'static;
+
+    /// Create a new instance of every middleware the router runs.
+    fn instances() -> Self::Instances;
+
+    /// The router's own `#[middleware(...)]` among `instances`.
+    fn router_middleware(instances: &Self::Instances) -> Vec<Arc<dyn Middleware>>;
+}
+
+/// A router serving requests with a state value attached, built by the generated `new()` (with
+/// `()` for state) and `with_state()`.
+///
+/// The state and the router's middleware are created once and shared by every request,
+/// so use interior mutability (`Mutex`, atomics, pools) for anything that changes.
+pub struct WithState<R: RouterMiddleware, S> {
+    router: R,
+    state: State<S>,
+    middleware: Arc<R::Instances>,
+}
+
+impl<R: RouterMiddleware, S> WithState<R, S> {
+    pub fn new(router: R, state: S) -> Self {
+        Self { router, state: State(Arc::new(state)), middleware: Arc::new(R::instances()) }
+    }
+}
+
+impl<R: RouterMiddleware + Display, S> Display for WithState<R, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { self.router.fmt(f) }
+}
+
+impl<R, S> Router for WithState<R, S>
+where
+    R: Router + RouterMiddleware,
+    S: Send + Sync + 'static,
+{
+    fn route(&self, mut req: Request<Incoming>) -> BoxFuture<'static, Result<Response<MilBody>, std::convert::Infallible>> {
+        req.extensions_mut().insert(self.state.clone());
+        req.extensions_mut().insert(self.middleware.clone());
+        self.router.route(req)
+    }
+
+    fn middleware(&self) -> Vec<Arc<dyn Middleware>> { R::router_middleware(&self.middleware) }
+
+    fn routes(&self) -> Vec<crate::RouteDescriptor> { self.router.routes() }
+}
@@ -0,0 +1,115 @@
+//! Drive a [`Router`] in-process, without binding a socket.
+//!
+//! Each request is served over an in-memory pipe by the same hyper connection
+//! code the real server uses, so middleware, auth and encoding all behave as
+//! they would over TCP, but there are no ports to pick and nothing to wait for.
+//!
+//! ```ignore
+//! let server = TestServer::new(MyRouter::new());
+//!
+//! let res = server.request(Request::put("/the_time").body(Bytes::new())?).await?;
+//! assert_eq!(res.status(), 200);
+//!
+//! // Or use the generated typed client on top of it.
+//! let client = MyRouter::client_with_transport("http://test".into(), Default::default(), server.clone());
+//! let now = client.the_time().await?;
+//! ```
+
+use {
+    crate::{ClientTransport, Router},
+    futures::{FutureExt, future::BoxFuture},
+    http_body_util::{BodyExt, Full},
+    hyper::{Request, Response, body::Bytes, server::conn::http1, service::service_fn},
+    hyper_util::rt::TokioIo,
+    serde::de::DeserializeOwned,
+    std::{ops::Deref, sync::Arc},
+};
+
+/// Size of the in-memory pipe between the test client and the router.
+const PIPE_CAPACITY: usize = 64 * 1024;
+
+/// Serves requests to a router over an in-memory connection.  Cheap to clone.
+///
+/// Requests must be sent from within a Tokio runtime.
+pub struct TestServer<R> {
+    router: Arc<R>,
+}
+
+impl<R> Clone for TestServer<R> {
+    fn clone(&self) -> Self { Self { router: self.router.clone() } }
+}
+
+impl<R> TestServer<R>
+where
+    R: Router + Send + Sync + 'static,
+{
+    pub fn new(router: R) -> Self { Self { router: Arc::new(router) } }
+
+    /// Send a single request to the router and collect the whole response.
+    ///
+    /// Only the path and query of the request URI are used.
+    pub async fn request(&self, req: Request<Bytes>) -> anyhow::Result<TestResponse> {
+        let (client_io, server_io) = tokio::io::duplex(PIPE_CAPACITY);
+
+        let router = self.router.clone();
+        tokio::spawn(async move {
+            let service = service_fn(move |req| router.route(req).map(|res| res.map(crate::served)));
+            if let Err(err) = http1::Builder::new().serve_connection(TokioIo::new(server_io), service).await {
+                tracing::warn!("Error serving test connection: {:?}", err);
+            }
+        });
+
+        let (mut sender, conn) = hyper::client::conn::http1::handshake(TokioIo::new(client_io)).await?;
+        tokio::spawn(conn);
+
+        let (mut parts, body) = req.into_parts();
+        parts.uri = parts.uri.path_and_query().map(|p| p.as_str()).unwrap_or("/").parse()?;
+
+        let res = sender.send_request(Request::from_parts(parts, Full::new(body))).await?;
+        let (parts, body) = res.into_parts();
+
+        Ok(TestResponse(Response::from_parts(parts, body.collect().await?.to_bytes())))
+    }
+}
+
+impl<R> ClientTransport for TestServer<R>
+where
+    R: Router + Send + Sync + 'static,
+{
+    fn send(&self, req: Request<Bytes>) -> BoxFuture<'static, anyhow::Result<Response<Bytes>>> {
+        let server = self.clone();
+        Box::pin(async move { Ok(server.request(req).await?.0) })
+    }
+}
+
+/// A fully-buffered response from a [`TestServer`].
+///
+/// Derefs to the underlying [`Response`] for status and header checks.
+#[derive(Debug)]
+pub struct TestResponse(pub Response<Bytes>);
+
+impl TestResponse {
+    /// The response body, gunzipped if the router compressed it.
+    pub fn bytes(&self) -> anyhow::Result<Bytes> {
+        match crate::is_gzipped(&self.0) {
+            true => {
+                let mut decompressed = Vec::new();
+                crate::gz_decompress(self.0.body().as_ref(), &mut decompressed)?;
+                Ok(Bytes::from(decompressed))
+            }
+            false => Ok(self.0.body().clone()),
+        }
+    }
+
+    /// The response body as UTF-8 text.
+    pub fn text(&self) -> anyhow::Result<String> { Ok(String::from_utf8(self.bytes()?.to_vec())?) }
+
+    /// The response body deserialized from JSON.
+    pub fn json<T: DeserializeOwned>(&self) -> anyhow::Result<T> { Ok(serde_json::from_slice(&self.bytes()?)?) }
+}
+
+impl Deref for TestResponse {
+    type Target = Response<Bytes>;
+
+    fn deref(&self) -> &Self::Target { &self.0 }
+}
@@ -0,0 +1,52 @@
+//! TLS termination for [`ServerBuilder`](crate::ServerBuilder), enabled by the `tls` feature.
+//!
+//! ```ignore
+//! milrouter::ServerBuilder::new(MyRouter::new())
+//!     .tls_pem("cert.pem", "key.pem")?
+//!     .serve("0.0.0.0:443".parse().unwrap())
+//!     .await?;
+//! ```
+
+pub use tokio_rustls::rustls;
+use {
+    crate::Protocol,
+    rustls::{
+        ServerConfig,
+        pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject},
+    },
+    std::{path::Path, sync::Arc},
+    tokio_rustls::TlsAcceptor,
+};
+
+/// Build a rustls [`ServerConfig`] from a PEM certificate chain and a PEM private key.
+///
+/// The chain file should start with the server's own certificate, followed by any intermediates.
+pub fn server_config_from_pem(cert: impl AsRef<Path>, key: impl AsRef<Path>) -> anyhow::Result<ServerConfig> {
+    let certs = CertificateDer::pem_file_iter(cert.as_ref())
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| anyhow::anyhow!("Failed to read certificates from {}: {e}", cert.as_ref().display()))?;
+    let key = PrivateKeyDer::from_pem_file(key.as_ref())
+        .map_err(|e| anyhow::anyhow!("Failed to read private key from {}: {e}", key.as_ref().display()))?;
+
+    Ok(ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)?)
+}
+
+/// Wrap `config` in an acceptor, advertising ALPN protocols to match `protocol`
+/// unless the config already lists its own.
+pub(crate) fn acceptor(config: Arc<ServerConfig>, protocol: Protocol) -> TlsAcceptor {
+    if !config.alpn_protocols.is_empty() {
+        return TlsAcceptor::from(config);
+    }
+
+    let mut config = (*config).clone();
+    config.alpn_protocols = match protocol {
+        Protocol::Http1 => vec![b"http/1.1".to_vec()],
+        Protocol::Http2 => vec![b"h2".to_vec()],
+        Protocol::Auto => vec![b"h2".to_vec(), b"http/1.1".to_vec()],
+    };
+
+    TlsAcceptor::from(Arc::new(config))
+}
@@ -0,0 +1,150 @@
+//! Interop with the tower ecosystem, enabled by the `tower` feature.
+//!
+//! [`RouterService`] turns any [`Router`] into a `tower::Service`, to mount it inside an axum or hyper
+//! application.  [`ServerBuilder::layer`](crate::ServerBuilder::layer) goes the other way, wrapping a
+//! router in tower layers (tower-http's timeouts, tracing, compression, CORS, concurrency limits) before
+//! serving it:
+//!
+//! ```ignore
+//! milrouter::ServerBuilder::new(MyRouter::new())
+//!     .layer(tower_http::timeout::TimeoutLayer::new(Duration::from_secs(10)))
+//!     .serve(addr)
+//!     .await?;
+//! ```
+//!
+//! Layers see the request before any of the router's middleware, and must leave its body as [`Incoming`].
+
+pub use {tower_layer::Layer, tower_service::Service};
+use {
+    crate::{BodyAbort, Middleware, MilBody, RouteDescriptor, Router},
+    bytes::Bytes,
+    futures::future::BoxFuture,
+    http_body_util::BodyExt,
+    hyper::{
+        Request,
+        Response,
+        body::{Body, Frame, Incoming, SizeHint},
+    },
+    std::{
+        convert::Infallible,
+        fmt::{self, Display},
+        pin::Pin,
+        sync::Arc,
+        task::{Context, Poll},
+    },
+};
+
+/// A [`Router`] as a `tower::Service`.  Cheap to clone; every clone routes with the same router.
+pub struct RouterService<R>(Arc<R>);
+
+impl<R> RouterService<R> {
+    pub fn new(router: R) -> Self { Self(Arc::new(router)) }
+}
+
+impl<R> Clone for RouterService<R> {
+    fn clone(&self) -> Self { Self(self.0.clone()) }
+}
+
+impl<R: Router> Service<Request<Incoming>> for RouterService<R> {
+    type Response = Response<MilBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<'static, Result<Response<MilBody>, Infallible>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> { Poll::Ready(Ok(())) }
+
+    fn call(&mut self, req: Request<Incoming>) -> Self::Future { self.0.route(req) }
+}
+
+/// A router wrapped in a tower layer, built by [`ServerBuilder::layer`](crate::ServerBuilder::layer)
+/// or [`Layered::new`].  Serves like any other [`Router`].
+///
+/// Errors from the layered service answer 500.  An error partway through a response body
+/// makes the server drop the connection where the body breaks off, so the client sees the
+/// response cut short rather than complete.
+pub struct Layered<R, S> {
+    router: Arc<R>,
+    service: S,
+}
+
+impl<R: Router> Layered<R, ()> {
+    pub fn new<L: Layer<RouterService<R>>>(router: R, layer: L) -> Layered<R, L::Service> {
+        Self::from_shared(Arc::new(router), layer)
+    }
+
+    pub(crate) fn from_shared<L: Layer<RouterService<R>>>(router: Arc<R>, layer: L) -> Layered<R, L::Service> {
+        Layered { service: layer.layer(RouterService(router.clone())), router }
+    }
+}
+
+impl<R: Display, S> Display for Layered<R, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { self.router.fmt(f) }
+}
+
+impl<R, S, B> Router for Layered<R, S>
+where
+    R: Router + Sync,
+    S: Service<Request<Incoming>, Response = Response<B>> + Clone + Send + Sync + 'static,
+    S::Error: Display + Send,
+    S::Future: Send,
+    B: Body<Data = Bytes> + Send + Sync + 'static,
+    B::Error: Display,
+{
+    fn route(&self, req: Request<Incoming>) -> BoxFuture<'static, Result<Response<MilBody>, Infallible>> {
+        let mut service = self.service.clone();
+        Box::pin(async move {
+            let response = match std::future::poll_fn(|cx| service.poll_ready(cx)).await {
+                Ok(()) => service.call(req).await,
+                Err(e) => Err(e),
+            };
+            Ok(match response {
+                Ok(response) => {
+                    let abort = BodyAbort::default();
+                    let mut response = response.map(|body| Lossy { body: Box::pin(body), abort: abort.clone() }.boxed());
+                    response.extensions_mut().insert(abort);
+                    response
+                }
+                Err(e) => {
+                    tracing::error!("[-] 500 Layered service error: {e}");
+                    Response::builder().status(500).body(crate::Body::from(e.to_string()).boxed()).unwrap()
+                }
+            })
+        })
+    }
+
+    fn middleware(&self) -> Vec<Arc<dyn Middleware>> { self.router.middleware() }
+
+    fn routes(&self) -> Vec<RouteDescriptor> { self.router.routes() }
+}
+
+/// A layer's response body with its errors logged, so it fits in a [`MilBody`].  An error
+/// ends the body and aborts it, so the server drops the connection rather than finish the response.
+struct Lossy<B> {
+    body: Pin<Box<B>>,
+    abort: BodyAbort,
+}
+
+impl<B> Body for Lossy<B>
+where
+    B: Body<Data = Bytes>,
+    B::Error: Display,
+{
+    type Data = Bytes;
+    type Error = Infallible;
+
+    fn poll_frame(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Frame<Bytes>, Infallible>>> {
+        match self.body.as_mut().poll_frame(cx) {
+            Poll::Ready(Some(Err(e))) => {
+                tracing::warn!("Error in layered response body, aborting it: {e}");
+                self.abort.abort();
+                Poll::Ready(None)
+            }
+            Poll::Ready(Some(Ok(frame))) => Poll::Ready(Some(Ok(frame))),
+            Poll::Ready(None) => Poll::Ready(None),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    fn is_end_stream(&self) -> bool { self.body.is_end_stream() }
+
+    fn size_hint(&self) -> SizeHint { self.body.size_hint() }
+}
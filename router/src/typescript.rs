@@ -0,0 +1,288 @@
+//! TypeScript clients for routers, built from their OpenAPI document.  Enabled by the `openapi` feature.
+//!
+//! The generated file has a type for every schema in the document and an async function per endpoint,
+//! using `fetch`.  Call it from a bin target or a `build.rs`:
+//!
+//! ```ignore
+//! milrouter::typescript::write("web/src/api.ts", &MyRouter::openapi())?;
+//! ```
+
+use {
+    serde_json::{Map, Value},
+    std::{fmt::Write, path::Path},
+};
+
+/// Shared by every generated file: request options, the error type, and the `fetch` plumbing.
+const RUNTIME: &str = r#"export interface RequestOptions {
+  /** Prepended to every endpoint path, e.g. `https://api.example.com`. */
+  baseUrl?: string;
+  headers?: Record<string, string>;
+  fetch?: typeof fetch;
+}
+
+/** Thrown for any non-2xx response.  `body` is the decoded JSON error, or the response text. */
+export class EndpointError<E = unknown> extends Error {
+  constructor(readonly status: number, readonly body: E | string) {
+    super(`${status}: ${typeof body === "string" ? body : JSON.stringify(body)}`);
+  }
+}
+
+const decoder = new TextDecoder();
+
+// Browsers decode `Content-Encoding: gzip` themselves, but not every runtime does.
+function isGzipped(res: Response, bytes: Uint8Array): boolean {
+  return res.headers.get("content-encoding") === "gzip" && bytes[0] === 0x1f && bytes[1] === 0x8b;
+}
+
+async function readBody(res: Response): Promise<Uint8Array> {
+  const bytes = new Uint8Array(await res.arrayBuffer());
+  if (!isGzipped(res, bytes)) return bytes;
+  const stream = new Blob([bytes]).stream().pipeThrough(new DecompressionStream("gzip"));
+  return new Uint8Array(await new Response(stream).arrayBuffer());
+}
+
+// Streamed responses are never compressed, so chunks are handed over as they arrive.
+async function readStream(res: Response): Promise<ReadableStream<Uint8Array>> {
+  return res.body!;
+}
+
+function query(data: unknown): string {
+  const params = new URLSearchParams();
+  for (const [key, value] of Object.entries((data ?? {}) as Record<string, unknown>)) {
+    for (const item of Array.isArray(value) ? value : [value]) {
+      if (item !== undefined && item !== null) params.append(key, String(item));
+    }
+  }
+  const encoded = params.toString();
+  return encoded ? `?${encoded}` : "";
+}
+
+async function send(method: string, path: string, data: unknown, options: RequestOptions = {}): Promise<Response> {
+  const get = method === "GET";
+  const url = `${options.baseUrl ?? ""}/${path}${get ? query(data) : ""}`;
+  const headers: Record<string, string> = { ...options.headers };
+  if (!get) headers["Content-Type"] = "application/json";
+
+  const res = await (options.fetch ?? fetch)(url, { method, headers, body: get ? undefined : JSON.stringify(data ?? null) });
+  if (!res.ok) {
+    const text = decoder.decode(await readBody(res));
+    const json = res.headers.get("content-type")?.startsWith("application/json");
+    throw new EndpointError(res.status, json ? JSON.parse(text) : text);
+  }
+  return res;
+}
+
+async function json<T>(res: Response): Promise<T> {
+  return JSON.parse(decoder.decode(await readBody(res)));
+}
+"#;
+
+/// Render the TypeScript client for an OpenAPI document produced by `Router::openapi()`.
+pub fn generate(doc: &Value) -> String {
+    let mut out = String::new();
+    let title = doc["info"]["title"].as_str().unwrap_or("router");
+    let _ = writeln!(out, "// Generated by milrouter from {title}.  Do not edit.\n");
+    out.push_str(RUNTIME);
+
+    if let Some(schemas) = doc["components"]["schemas"].as_object() {
+        for (name, schema) in schemas {
+            out.push('\n');
+            write_doc(&mut out, "", schema["description"].as_str());
+            match is_interface(schema) {
+                true => {
+                    let _ = writeln!(out, "export interface {} {{", ident(name));
+                    for (property, ty, optional) in properties(schema) {
+                        write_doc(&mut out, "  ", schema["properties"][property]["description"].as_str());
+                        let _ = writeln!(out, "  {}{optional}: {ty};", property_name(property));
+                    }
+                    out.push_str("}\n");
+                }
+                false => {
+                    let _ = writeln!(out, "export type {} = {};", ident(name), ts_type(schema));
+                }
+            }
+        }
+    }
+
+    for (path, item) in doc["paths"].as_object().into_iter().flatten() {
+        for (method, op) in item.as_object().into_iter().flatten() {
+            out.push('\n');
+            write_operation(&mut out, path, &method.to_ascii_uppercase(), op);
+        }
+    }
+
+    out
+}
+
+/// [`generate`] the client and write it to `path`, for use from `build.rs`.
+pub fn write(path: impl AsRef<Path>, doc: &Value) -> std::io::Result<()> { std::fs::write(path, generate(doc)) }
+
+fn write_operation(out: &mut String, path: &str, method: &str, op: &Value) {
+    let parameters = op["parameters"].as_array().map(Vec::as_slice).unwrap_or_default();
+    let params = parameters.iter().filter(|p| p["in"] == "path").collect::<Vec<_>>();
+
+    let mut args = params
+        .iter()
+        .map(|p| format!("{}: {}", camel_case(p["name"].as_str().unwrap_or_default()), ts_type(&p["schema"])))
+        .collect::<Vec<_>>();
+
+    let input = match method {
+        "GET" => parameters.iter().find(|p| p["in"] == "query").map(|p| &p["schema"]),
+        _ => op["requestBody"]["content"]["application/json"].get("schema"),
+    };
+    if let Some(schema) = input {
+        args.push(format!("data: {}", ts_type(schema)));
+    }
+    args.push("options?: RequestOptions".into());
+
+    let ok = &op["responses"]["200"];
+    let (returns, decode) = match ok["content"]["application/json"].get("schema") {
+        Some(schema) => (ts_type(schema), "json(res)"),
+        None if ok["x-milrouter-stream"] == true => ("ReadableStream<Uint8Array>".into(), "readStream(res)"),
+        None => ("Uint8Array".into(), "readBody(res)"),
+    };
+    let error = match op["responses"]["default"]["content"]["application/json"].get("schema") {
+        Some(schema) => ts_type(schema),
+        None => "string".into(),
+    };
+
+    let mut url = path.trim_start_matches('/').to_string();
+    for p in &params {
+        let name = p["name"].as_str().unwrap_or_default();
+        url = url.replace(&format!("{{{name}}}"), &format!("${{encodeURIComponent(String({}))}}", camel_case(name)));
+    }
+    let data = match input {
+        Some(_) => "data",
+        None => "null",
+    };
+
+    let _ = writeln!(out, "/** `{method} {path}`\n * @throws {{EndpointError<{error}>}} */");
+    let _ = writeln!(
+        out,
+        "export async function {}({}): Promise<{returns}> {{",
+        camel_case(op["operationId"].as_str().unwrap_or_default()),
+        args.join(", ")
+    );
+    let _ = writeln!(out, "  const res = await send(\"{method}\", `{url}`, {data}, options);");
+    let _ = writeln!(out, "  return {decode};\n}}");
+}
+
+fn write_doc(out: &mut String, indent: &str, description: Option<&str>) {
+    if let Some(description) = description {
+        let _ = writeln!(out, "{indent}/** {} */", description.replace("*/", "*\\/").replace('\n', &format!("\n{indent} * ")));
+    }
+}
+
+/// Named object schemas become interfaces; everything else becomes a type alias.
+fn is_interface(schema: &Value) -> bool {
+    schema["type"] == "object"
+        && schema.get("properties").is_some()
+        && ["oneOf", "anyOf", "allOf", "additionalProperties"].iter().all(|k| schema.get(k).is_none_or(|v| v == false))
+}
+
+fn ts_type(schema: &Value) -> String {
+    let schema = match schema {
+        Value::Bool(true) => return "unknown".into(),
+        Value::Bool(false) => return "never".into(),
+        Value::Object(schema) => schema,
+        _ => return "unknown".into(),
+    };
+
+    if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+        return ident(reference.rsplit('/').next().unwrap_or(reference));
+    }
+    if let Some(value) = schema.get("const") {
+        return value.to_string();
+    }
+    if let Some(values) = schema.get("enum").and_then(Value::as_array) {
+        return values.iter().map(Value::to_string).collect::<Vec<_>>().join(" | ");
+    }
+    for (key, separator) in [("oneOf", " | "), ("anyOf", " | "), ("allOf", " & ")] {
+        if let Some(variants) = schema.get(key).and_then(Value::as_array) {
+            return variants.iter().map(|v| group(ts_type(v))).collect::<Vec<_>>().join(separator);
+        }
+    }
+
+    match schema.get("type") {
+        Some(Value::String(ty)) => primitive(ty, schema),
+        Some(Value::Array(types)) => {
+            types.iter().filter_map(Value::as_str).map(|ty| group(primitive(ty, schema))).collect::<Vec<_>>().join(" | ")
+        }
+        _ if schema.contains_key("properties") => primitive("object", schema),
+        _ => "unknown".into(),
+    }
+}
+
+fn primitive(ty: &str, schema: &Map<String, Value>) -> String {
+    match ty {
+        "string" => "string".into(),
+        "integer" | "number" => "number".into(),
+        "boolean" => "boolean".into(),
+        "null" => "null".into(),
+        "array" => match (schema.get("prefixItems").and_then(Value::as_array), schema.get("items")) {
+            (Some(items), _) => format!("[{}]", items.iter().map(ts_type).collect::<Vec<_>>().join(", ")),
+            (None, Some(items)) => format!("{}[]", group(ts_type(items))),
+            (None, None) => "unknown[]".into(),
+        },
+        "object" => match (schema.contains_key("properties"), schema.get("additionalProperties")) {
+            (true, _) => {
+                let schema = Value::Object(schema.clone());
+                let fields = properties(&schema)
+                    .into_iter()
+                    .map(|(property, ty, optional)| format!("{}{optional}: {ty}", property_name(property)))
+                    .collect::<Vec<_>>();
+                format!("{{ {} }}", fields.join("; "))
+            }
+            (false, Some(values)) if values != &Value::Bool(false) => format!("Record<string, {}>", ts_type(values)),
+            (false, _) => "Record<string, unknown>".into(),
+        },
+        _ => "unknown".into(),
+    }
+}
+
+/// Each property of an object schema with its type, and `?` unless it is required.
+fn properties(schema: &Value) -> Vec<(&str, String, &'static str)> {
+    let required = schema["required"].as_array().map(Vec::as_slice).unwrap_or_default();
+    schema["properties"]
+        .as_object()
+        .into_iter()
+        .flatten()
+        .map(|(name, property)| {
+            let optional = match required.iter().any(|r| r == name) {
+                true => "",
+                false => "?",
+            };
+            (name.as_str(), ts_type(property), optional)
+        })
+        .collect()
+}
+
+/// Parenthesize unions and intersections so they can be nested.
+fn group(ty: String) -> String {
+    match ty.contains(" | ") || ty.contains(" & ") {
+        true => format!("({ty})"),
+        false => ty,
+    }
+}
+
+fn ident(name: &str) -> String { name.chars().map(|c| if c.is_ascii_alphanumeric() || c == '_' { c } else { '_' }).collect() }
+
+fn property_name(name: &str) -> String {
+    let valid = name.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '$')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$');
+    match valid {
+        true => name.into(),
+        false => Value::String(name.into()).to_string(),
+    }
+}
+
+fn camel_case(name: &str) -> String {
+    let mut parts = name.split('_').filter(|p| !p.is_empty());
+    let first = parts.next().unwrap_or_default().to_string();
+    parts.fold(first, |mut out, part| {
+        let mut chars = part.chars();
+        out.extend(chars.next().map(|c| c.to_ascii_uppercase()));
+        out.push_str(chars.as_str());
+        out
+    })
+}
@@ -1,5 +1,5 @@
 use {
-    crate::{Endpoint, IntoRouter, Router},
+    crate::{EndpointArgs, IntoRouter, Router},
     anyhow::anyhow,
     futures::{FutureExt, future::BoxFuture},
     futures_signals::signal::Mutable,
@@ -53,34 +53,58 @@ impl<R: Send + 'static> WasmReponse<R> {
     }
 }
 
+/// Prepended to every [`request`] path; set with [`set_base_path`].
+static BASE_PATH: std::sync::RwLock<String> = std::sync::RwLock::new(String::new());
+
+/// Send every [`request`] under `path`, for a router served with `milrouter::Mount` (e.g. `"/api/v1"`).
+/// Leading and trailing slashes are optional.
+pub fn set_base_path(path: impl AsRef<str>) {
+    let path = path.as_ref().trim_matches('/');
+    *BASE_PATH.write().unwrap() = match path.is_empty() {
+        true => String::new(),
+        false => format!("/{path}"),
+    };
+}
+
 /// This is one way to make requests.
 /// You may (and probably should) customise this to fir your needs.
-pub fn request<R, C, E>(endpoint: E, data: E::Data) -> WasmReponse<E::Returns>
+///
+/// `args` are the arguments the native client method takes one by one, as a single value:
+/// the path parameters, then the body arguments.  Pass `()` for none, the value itself for one
+/// and a tuple for more, e.g. `("ab".to_string(), 3)` for `client.repeat("ab".to_string(), 3)`.
+pub fn request<R, C, E>(endpoint: E, args: E::Args) -> WasmReponse<E::Returns>
 where
-    E: Endpoint<C> + IntoRouter<R> + Send + 'static,
-    E::Data: Send + 'static,
+    E: EndpointArgs<C> + IntoRouter<R> + Send + 'static,
+    E::Args: Send + 'static,
     E::Returns: serde::de::DeserializeOwned + 'static,
     R: Router,
 {
-    let base_url = web_sys::window().unwrap().origin();
+    let base_url = format!("{}{}", web_sys::window().unwrap().origin(), BASE_PATH.read().unwrap());
     let (tx, rx) = tokio::sync::oneshot::channel::<anyhow::Result<E::Returns>>();
 
     spawn_local(async move {
         let _ = tx.send(
             async move {
-                let req = reqwest::Client::new()
-                    .request(
-                        match E::is_idempotent() {
-                            true => reqwest::Method::PUT,
-                            false => reqwest::Method::POST,
-                        },
-                        format!("{base_url}/{}", endpoint.router()),
-                    )
-                    .header("Connection", "Keep-Alive")
-                    .header("Keep-Alive", "timeout=600")
-                    .json(&data)
-                    .send()
-                    .await?;
+                let (path, data) = E::encode_args(args)?;
+                let url = format!("{base_url}/{}", match path {
+                    Some(path) => path,
+                    None => endpoint.router().to_string(),
+                });
+
+                let req = match E::method() {
+                    reqwest::Method::GET => {
+                        let query = crate::encode_query(&data)?;
+                        reqwest::Client::new().get(match query.is_empty() {
+                            true => url,
+                            false => format!("{url}?{query}"),
+                        })
+                    }
+                    method => reqwest::Client::new().request(method, url).json(&data),
+                }
+                .header("Connection", "Keep-Alive")
+                .header("Keep-Alive", "timeout=600")
+                .send()
+                .await?;
 
                 let resp = req.text().await?;
 
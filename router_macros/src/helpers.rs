@@ -14,6 +14,10 @@ macro_rules! err {
     };
 }
 
+/// The most arguments a generated client method takes: an endpoint's path parameters and body
+/// arguments together.
+pub const MAX_CLIENT_ARGS: usize = 12;
+
 pub fn unit() -> Type { syn::parse_str("()").unwrap() }
 
 pub fn preamble(input: DeriveInput) -> (DeriveInput, Ident, DataEnum) {
@@ -51,6 +55,27 @@ pub fn get_inner_type(t: Type) -> Result<Type, syn::Error> {
     }
 }
 
+/// The `E` of a `Result<T, E>` return type, or `None` for `anyhow::Result<T>` / `Result<T, anyhow::Error>`.
+pub fn get_error_type(t: &Type) -> Option<Type> {
+    let Type::Path(p) = t else { return None };
+    let syn::PathArguments::AngleBracketed(args) = &p.path.segments.last()?.arguments else { return None };
+
+    match args.args.iter().nth(1)? {
+        syn::GenericArgument::Type(Type::Path(e)) if e.path.segments.iter().any(|s| s.ident == "anyhow") => None,
+        syn::GenericArgument::Type(e) => Some(e.clone()),
+        _ => None,
+    }
+}
+
+/// A type as written in the source, e.g. `Vec<String>`, without the token spacing of `to_string()`.
+pub fn type_name(t: &Type) -> String {
+    let mut name = t.to_token_stream().to_string();
+    for (from, to) in [(" <", "<"), ("< ", "<"), (" >", ">"), (" ,", ","), (" :: ", "::"), (":: ", "::"), ("& ", "&")] {
+        name = name.replace(from, to);
+    }
+    name
+}
+
 pub fn type_contains(t: Type, s: String) -> bool {
     match t {
         Type::Path(ref p) => p.path.segments.iter().any(|p| match p.arguments.clone() {
@@ -69,11 +94,17 @@ pub fn type_contains(t: Type, s: String) -> bool {
 #[derive(Debug)]
 pub struct RouteInfo {
     pub is_idempotent: bool,
+    /// `#[endpoint(method = get)]` — `Data` is read from the query string.
+    pub is_get: bool,
     pub auth: proc_macro2::TokenStream,
     /// `#[endpoint(raw, ...)]` — serve response as raw bytes, skip JSON.
     pub raw: bool,
     /// `#[endpoint(stream, ...)]` — serve response as a streaming body.
     pub stream: bool,
+    /// `#[endpoint(path = "/users/{id}")]` — route pattern replacing the variant name.
+    pub path: Option<String>,
+    /// `#[endpoint(middleware(RateLimit, Audit))]` — middleware for this endpoint only.
+    pub middleware: Vec<Ident>,
 }
 
 impl RouteInfo {
@@ -132,20 +163,39 @@ impl RouteInfo {
 
                     el => return Err(syn::Error::new_spanned(token, format!("Unexpected punctuation mark: {el}"))),
                 },
+                // `key(a, b)` lists, kept whole as the key's value.
+                proc_macro2::TokenTree::Group(g) if g.delimiter() == proc_macro2::Delimiter::Parenthesis && buf.len() == 1 => {
+                    map.insert(buf.remove(0), (g.stream().to_string(), g.stream()));
+                    tbuf = proc_macro2::TokenStream::new();
+                }
                 _ => return Err(syn::Error::new_spanned(token, "I have no idea what this guy is doing here")),
             }
         }
 
         RouteInfo::parse_groups(&mut map, &mut buf, &mut tbuf)?;
 
+        let is_get = match map.get("method").cloned() {
+            None => false,
+            Some((m, _)) if m.eq_ignore_ascii_case("get") => true,
+            Some((_, t)) => {
+                return Err(syn::Error::new_spanned(
+                    t,
+                    "Attribute 'method' only supports `get`; use `idempotent` to choose between PUT and POST",
+                ));
+            }
+        };
+
         Ok(RouteInfo {
             is_idempotent: {
                 let (v, t) = map.get("idempotent").cloned().unwrap_or((false.to_string(), Default::default()));
 
-                v.parse::<bool>()
-                    .map_err(|_| syn::Error::new_spanned(t, "Attribute 'idempotent' must be a valid boolean"))?
+                is_get
+                    || v.parse::<bool>()
+                        .map_err(|_| syn::Error::new_spanned(t, "Attribute 'idempotent' must be a valid boolean"))?
             },
 
+            is_get,
+
             auth: {
                 map.get("auth").cloned().map(|a| proc_macro2::TokenStream::from_str(&a.0).unwrap()).ok_or(
                     syn::Error::new_spanned(
@@ -157,15 +207,56 @@ impl RouteInfo {
 
             raw: map.contains_key("raw"),
             stream: map.contains_key("stream"),
+
+            path: match map.get("path").cloned() {
+                None => None,
+                Some((p, t)) if p == "true" => {
+                    return Err(syn::Error::new_spanned(t, "Attribute 'path' needs a value, e.g. path = \"/users/{id}\""));
+                }
+                Some((p, t)) => match route_params(&p).iter().all(|n| syn::parse_str::<Ident>(n).is_ok()) {
+                    true => Some(p),
+                    false => return Err(syn::Error::new_spanned(t, "Path parameters must be identifiers, e.g. {user_id}")),
+                },
+            },
+
+            middleware: match map.get("middleware").cloned() {
+                None => Vec::new(),
+                Some((_, t)) => {
+                    let parser = syn::punctuated::Punctuated::<Ident, syn::Token![,]>::parse_terminated;
+                    syn::parse::Parser::parse2(parser, t.clone())
+                        .map_err(|_| syn::Error::new_spanned(t, "Attribute 'middleware' should list middleware types, e.g. middleware(RateLimit)"))?
+                        .into_iter()
+                        .collect()
+                }
+            },
         })
     }
+
+    /// The `{name}` placeholders of the `path` pattern, in order.
+    pub fn params(&self) -> Vec<String> { self.path.as_deref().map(route_params).unwrap_or_default() }
+}
+
+fn route_params(pattern: &str) -> Vec<String> {
+    pattern
+        .trim_matches('/')
+        .split('/')
+        .filter_map(|s| s.strip_prefix('{').and_then(|s| s.strip_suffix('}')))
+        .map(|s| s.to_string())
+        .collect()
 }
 
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct PartialFnArgs {
     pub client: Option<(Ident, Type)>,
-    pub input: (Ident, Type),
+    /// The body arguments, in declaration order.  More than one are sent as a synthesized struct.
+    pub inputs: Vec<(Ident, Type)>,
     pub headers: Option<Ident>,
+    /// The router's shared state, as `&S` or `State<S>`.
+    pub state: Option<(Ident, Type)>,
+    /// `Extension<T>` arguments, filled from values middleware put in the request's context.
+    pub extensions: Vec<(Ident, Type)>,
+    /// Arguments filled from path parameters, in pattern order.
+    pub params: Vec<(Ident, Type)>,
 }
 
 impl PartialFnArgs {
@@ -195,34 +286,180 @@ impl PartialFnArgs {
             push(quote!(#i: milrouter::hyper::HeaderMap));
         }
 
-        let (i, t) = self.input.clone();
-        push(quote!(#i: #t));
+        if let Some((i, t)) = self.state.clone() {
+            push(quote!(#i: #t));
+        }
+
+        for (i, t) in self.extensions.clone() {
+            push(quote!(#i: #t));
+        }
+
+        for (i, t) in self.params.clone() {
+            push(quote!(#i: #t));
+        }
+
+        if self.inputs.is_empty() {
+            let i = format_ident!("_");
+            let t = unit();
+            push(quote!(#i: #t));
+        }
+
+        for (i, t) in self.inputs.clone() {
+            push(quote!(#i: #t));
+        }
 
         ts
     }
 }
 
-impl Default for PartialFnArgs {
-    fn default() -> Self { Self { client: None, input: (format_ident!("_"), unit()), headers: None } }
+/// The `S` of a `&S` or `State<S>` argument.
+///
+/// Only a shared reference to a named type counts as `&S`: `&str`, `&[T]`, `&mut S`
+/// and references to primitives are never state.
+pub fn state_type(t: &Type) -> Option<Type> {
+    match t {
+        Type::Reference(r) if r.mutability.is_none() && is_named_type(&r.elem) => Some(*r.elem.clone()),
+        _ => wrapped_type(t, "State"),
+    }
+}
+
+/// `true` for a path type such as `AppState` or `crate::AppState` that isn't a primitive.
+fn is_named_type(t: &Type) -> bool {
+    const PRIMITIVES: &[&str] = &[
+        "str", "bool", "char", "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32", "i64", "i128", "isize", "f32",
+        "f64",
+    ];
+    match t {
+        Type::Path(p) if p.qself.is_none() => !p.path.get_ident().is_some_and(|i| PRIMITIVES.contains(&i.to_string().as_str())),
+        _ => false,
+    }
+}
+
+/// The `T` of an `Extension<T>` argument.
+pub fn extension_type(t: &Type) -> Option<Type> { wrapped_type(t, "Extension") }
+
+/// The `T` of a `Wrapper<T>` type, matching the wrapper by its last path segment.
+fn wrapped_type(t: &Type, wrapper: &str) -> Option<Type> {
+    let Type::Path(p) = t else { return None };
+    let last = p.path.segments.last()?;
+    let syn::PathArguments::AngleBracketed(args) = &last.arguments else { return None };
+    match (last.ident == wrapper, args.args.first()?) {
+        (true, syn::GenericArgument::Type(s)) => Some(s.clone()),
+        _ => None,
+    }
+}
+
+/// An explicit `#[auth]`, `#[headers]` or `#[body]` marker on an endpoint argument.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ArgRole {
+    Auth,
+    Headers,
+    Body,
+}
+
+impl ArgRole {
+    fn name(self) -> &'static str {
+        match self {
+            ArgRole::Auth => "#[auth]",
+            ArgRole::Headers => "#[headers]",
+            ArgRole::Body => "#[body]",
+        }
+    }
+}
+
+/// The role marker among an argument's attributes, if any.
+pub fn arg_role(attrs: &[syn::Attribute]) -> Result<Option<ArgRole>, syn::Error> {
+    let mut role = None;
+    for attr in attrs {
+        let this = match attr.path().get_ident().map(|i| i.to_string()).as_deref() {
+            Some("auth") => ArgRole::Auth,
+            Some("headers") => ArgRole::Headers,
+            Some("body") => ArgRole::Body,
+            _ => continue,
+        };
+        attr.meta.require_path_only()?;
+        if let Some(other) = role.replace(this) {
+            return Err(syn::Error::new_spanned(
+                attr,
+                format!("Argument is marked both {} and {}; pick one", ArgRole::name(other), this.name()),
+            ));
+        }
+    }
+    Ok(role)
 }
 
-pub fn parse_fn_args(a: Vec<(Ident, Type)>) -> PartialFnArgs {
-    a.iter().fold(PartialFnArgs::default(), |mut a, b| {
-        let is_client_ty =
-            type_contains(b.1.clone(), "client".to_string()) || b.0.to_string().to_lowercase().contains("client");
-
-        if is_client_ty {
-            a.client.replace(b.clone());
-        } else if b.0.to_string().to_lowercase().contains("header") {
-            a.headers.replace(b.0.clone());
-        } else if a.input.0 != "_" {
-            panic!("Unexpected non-client argument {}, as input is already defined as {}", b.0, a.input.1.to_token_stream())
+/// Sort an endpoint's arguments into their roles.
+///
+/// Explicitly marked arguments are placed first.  The rest are path parameters (by name),
+/// state (`&S` or `State<S>`, at most one), middleware extensions (`Extension<T>`), then, for roles
+/// not already taken, the auth client (name or type contains "client") and headers (name contains "header").
+/// Everything else is the body, unless some arguments are marked `#[body]`: then any left over is an error.
+pub fn parse_fn_args(a: Vec<(Ident, Type, Option<ArgRole>)>, params: &[String]) -> Result<PartialFnArgs, syn::Error> {
+    let mut args = PartialFnArgs::default();
+
+    for (ident, ty, role) in a.iter().filter(|(.., role)| role.is_some()) {
+        let role = role.unwrap();
+        if params.contains(&ident.to_string()) {
+            return Err(syn::Error::new_spanned(
+                ident,
+                format!("`{ident}` is a path parameter and can't also be marked {}", role.name()),
+            ));
+        }
+
+        let previous = match role {
+            ArgRole::Auth => args.client.replace((ident.clone(), ty.clone())).map(|(i, _)| i),
+            ArgRole::Headers => args.headers.replace(ident.clone()),
+            ArgRole::Body => {
+                args.inputs.push((ident.clone(), ty.clone()));
+                None
+            }
+        };
+        if let Some(previous) = previous {
+            return Err(syn::Error::new_spanned(
+                ident,
+                format!("Both `{previous}` and `{ident}` are marked {}", role.name()),
+            ));
+        }
+    }
+
+    for (ident, ty, _) in a.iter().filter(|(.., role)| role.is_none()) {
+        let is_client_ty = type_contains(ty.clone(), "client".to_string()) || ident.to_string().to_lowercase().contains("client");
+
+        if params.contains(&ident.to_string()) {
+            args.params.push((ident.clone(), ty.clone()));
+        } else if state_type(ty).is_some() {
+            if let Some((previous, _)) = args.state.replace((ident.clone(), ty.clone())) {
+                return Err(syn::Error::new_spanned(
+                    ident,
+                    format!("Both `{previous}` and `{ident}` take the router's state; an endpoint can only have one"),
+                ));
+            }
+        } else if let Type::Reference(_) = ty {
+            return Err(syn::Error::new_spanned(
+                ty,
+                format!("`{ident}` is a reference; endpoint arguments are taken by value, except the router's state (`&S`)"),
+            ));
+        } else if extension_type(ty).is_some() {
+            args.extensions.push((ident.clone(), ty.clone()));
+        } else if is_client_ty && args.client.is_none() {
+            args.client.replace((ident.clone(), ty.clone()));
+        } else if ident.to_string().to_lowercase().contains("header") && args.headers.is_none() {
+            args.headers.replace(ident.clone());
+        } else if a.iter().any(|(.., role)| *role == Some(ArgRole::Body)) {
+            return Err(syn::Error::new_spanned(
+                ident,
+                format!(
+                    "Unexpected argument `{ident}`, as the body arguments are marked #[body]. Mark it with #[auth], #[headers] or #[body] to say which it is"
+                ),
+            ));
         } else {
-            a.input = b.clone()
+            args.inputs.push((ident.clone(), ty.clone()));
         }
+    }
 
-        a
-    })
+    args.params.sort_by_key(|(i, _)| params.iter().position(|p| i == p));
+    args.inputs.sort_by_key(|(i, _)| a.iter().position(|(j, ..)| i == j));
+    Ok(args)
 }
 
 fn strip(a: &str) -> String {
@@ -265,3 +502,103 @@ pub fn parse_attrs(input: DeriveInput) -> (Option<TokenStream>, Option<String>,
 
     (html, local_assets, mware)
 }
+
+/// `#[group(expensive(RateLimit, Audit))]` on a router: named lists of middleware
+/// that variants join with `#[group(expensive)]`.
+pub fn parse_group_attrs(input: &DeriveInput) -> Result<HashMap<String, Vec<Ident>>, syn::Error> {
+    let mut groups = HashMap::new();
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("group")) {
+        let error = || syn::Error::new_spanned(attr, "Group attribute should name groups and their middleware, e.g. #[group(expensive(RateLimit))]");
+        let lists = attr
+            .parse_args_with(syn::punctuated::Punctuated::<syn::MetaList, syn::Token![,]>::parse_terminated)
+            .map_err(|_| error())?;
+
+        for list in lists {
+            let name = list.path.get_ident().ok_or_else(error)?.to_string();
+            let middleware = list
+                .parse_args_with(syn::punctuated::Punctuated::<Ident, syn::Token![,]>::parse_terminated)
+                .map_err(|_| error())?;
+            if groups.insert(name.clone(), middleware.into_iter().collect()).is_some() {
+                return Err(syn::Error::new_spanned(list, format!("Group `{name}` is defined more than once")));
+            }
+        }
+    }
+    Ok(groups)
+}
+
+/// The groups a router variant joins with `#[group(...)]`, in order.
+pub fn variant_groups(variant: &syn::Variant, groups: &HashMap<String, Vec<Ident>>) -> Result<Vec<Ident>, syn::Error> {
+    let mut joined = Vec::new();
+    for attr in variant.attrs.iter().filter(|a| a.path().is_ident("group")) {
+        let names = attr
+            .parse_args_with(syn::punctuated::Punctuated::<Ident, syn::Token![,]>::parse_terminated)
+            .map_err(|_| syn::Error::new_spanned(attr, "Variant group attribute should list group names, e.g. #[group(expensive)]"))?;
+        for name in names {
+            if !groups.contains_key(&name.to_string()) {
+                return Err(syn::Error::new_spanned(&name, format!("No group `{name}`; define it on the router with #[group({name}(...))]")));
+            }
+            joined.push(name);
+        }
+    }
+    Ok(joined)
+}
+
+/// `#[nest]` or `#[nest("/admin")]` on a router variant holding another router: the prefix the child
+/// is routed under, defaulting to the variant's snake-case name.  `None` for endpoint variants.
+pub fn variant_nest(variant: &syn::Variant) -> Result<Option<String>, syn::Error> {
+    let Some(attr) = variant.attrs.iter().find(|a| a.path().is_ident("nest")) else { return Ok(None) };
+    let prefix = match &attr.meta {
+        syn::Meta::Path(_) => heck::AsSnekCase(variant.ident.to_string()).to_string(),
+        _ => attr
+            .parse_args::<syn::LitStr>()
+            .map(|l| l.value().trim_matches('/').to_string())
+            .map_err(|_| syn::Error::new_spanned(attr, "Nest attribute takes an optional literal prefix, e.g. #[nest(\"/admin\")]"))?,
+    };
+    match prefix.is_empty() {
+        true => Err(syn::Error::new_spanned(attr, "A nested router needs a non-empty prefix")),
+        false => Ok(Some(prefix)),
+    }
+}
+
+/// The client `#[derive(Router)]` generates for a router type: `path::AdminRouter` -> `path::AdminRouterClient`.
+pub fn client_type(ty: &Type) -> Result<Type, syn::Error> {
+    let Type::Path(path) = ty else { return Err(syn::Error::new_spanned(ty, "Nested routers must be named by a path")) };
+    let mut path = path.clone();
+    let last = path.path.segments.last_mut().ok_or_else(|| syn::Error::new_spanned(ty, "Expected a router type"))?;
+    last.ident = format_ident!("{}Client", last.ident);
+    last.arguments = syn::PathArguments::None;
+    Ok(Type::Path(path))
+}
+
+/// The field of a router's middleware instances holding a group's.
+pub fn group_field(name: &Ident) -> Ident { format_ident!("group_{}", name) }
+
+/// The field of a router's middleware instances holding a variant's endpoint or nested router's.
+pub fn variant_field(variant: &Ident) -> Ident { format_ident!("variant_{}", heck::AsSnekCase(variant.to_string()).to_string()) }
+
+/// `#[openapi("/openapi.json")]` on a router: the path to serve the OpenAPI document at.
+pub fn parse_openapi_attr(input: &DeriveInput) -> Result<Option<String>, syn::Error> {
+    input
+        .attrs
+        .iter()
+        .find(|a| a.path().is_ident("openapi"))
+        .map(|a| {
+            a.parse_args::<syn::LitStr>().map(|l| l.value()).map_err(|_| {
+                syn::Error::new_spanned(a.into_token_stream(), "OpenAPI attribute should be a literal path, e.g. \"/openapi.json\"")
+            })
+        })
+        .transpose()
+}
+
+/// `#[fallback(not_found)]` on a router: the async handler answering requests no route matched.
+pub fn parse_fallback_attr(input: &DeriveInput) -> Result<Option<syn::Expr>, syn::Error> {
+    input
+        .attrs
+        .iter()
+        .find(|a| a.path().is_ident("fallback"))
+        .map(|a| {
+            a.parse_args::<syn::Expr>()
+                .map_err(|_| syn::Error::new_spanned(a.into_token_stream(), "Fallback attribute should point to a function"))
+        })
+        .transpose()
+}
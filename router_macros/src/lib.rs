@@ -7,7 +7,7 @@ pub fn endpoint(annot: TokenStream, item: TokenStream) -> TokenStream {
     macro_impl::endpoint::expand_endpoint(annot, item)
 }
 
-#[proc_macro_derive(Router, attributes(assets, html, middleware))]
+#[proc_macro_derive(Router, attributes(assets, fallback, group, html, middleware, nest, openapi))]
 pub fn router(item: TokenStream) -> TokenStream {
     macro_impl::router::expand_router(item)
 }
@@ -15,10 +15,22 @@ pub fn router(item: TokenStream) -> TokenStream {
 #[proc_macro_attribute]
 pub fn assets(_: TokenStream, i: TokenStream) -> TokenStream { i }
 
+#[proc_macro_attribute]
+pub fn fallback(_: TokenStream, i: TokenStream) -> TokenStream { i }
+
 #[proc_macro_attribute]
 pub fn html(_: TokenStream, i: TokenStream) -> TokenStream { i }
 
+#[proc_macro_attribute]
+pub fn group(_: TokenStream, i: TokenStream) -> TokenStream { i }
+
 #[proc_macro_attribute]
 pub fn middleware(_: TokenStream, i: TokenStream) -> TokenStream { i }
 
+#[proc_macro_attribute]
+pub fn nest(_: TokenStream, i: TokenStream) -> TokenStream { i }
+
+#[proc_macro_attribute]
+pub fn openapi(_: TokenStream, i: TokenStream) -> TokenStream { i }
+
 use proc_macro::TokenStream;
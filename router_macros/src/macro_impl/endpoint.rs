@@ -1,8 +1,10 @@
 use {
     heck::AsPascalCase,
-    crate::helpers::{RouteInfo, get_inner_type, parse_fn_args, unit},
+    crate::helpers::{
+        MAX_CLIENT_ARGS, RouteInfo, arg_role, extension_type, get_error_type, get_inner_type, parse_fn_args, state_type, type_name, unit,
+    },
     proc_macro::TokenStream,
-    quote::{ToTokens, quote},
+    quote::{ToTokens, format_ident, quote},
     syn::{FnArg, parse_macro_input},
 };
 
@@ -14,7 +16,10 @@ pub fn expand_endpoint(annot: TokenStream, item: TokenStream) -> TokenStream {
     let ret = meta.sig.clone().output;
     let block = meta.block;
 
-    let args = parse_fn_args(
+    let info = err!(RouteInfo::parse(annot.into()));
+    let route_params = info.params();
+
+    let args = err!(
         meta.sig
             .inputs
             .iter()
@@ -31,17 +36,27 @@ pub fn expand_endpoint(annot: TokenStream, item: TokenStream) -> TokenStream {
 
                 let ty = *a.clone().ty;
 
-                (ident, ty)
+                Ok((ident, ty, arg_role(&a.attrs)?))
             })
-            .collect::<Vec<_>>(),
+            .collect::<Result<Vec<_>, syn::Error>>()
+            .and_then(|a| parse_fn_args(a, &route_params))
     );
 
-    let info = err!(RouteInfo::parse(annot.into()));
+    if let Some(missing) = route_params.iter().find(|p| !args.params.iter().any(|(i, _)| i == *p)) {
+        return syn::Error::new_spanned(
+            meta.sig.inputs.to_token_stream(),
+            format!("Path parameter `{{{missing}}}` has no matching function argument"),
+        )
+        .into_compile_error()
+        .into();
+    }
+
     let (idempotent, auth, is_raw, is_stream) = (info.is_idempotent, info.auth, info.raw, info.stream);
 
-    let method = match idempotent {
-        true => "PUT",
-        false => "POST",
+    let method = match (info.is_get, idempotent) {
+        (true, _) => "GET",
+        (_, true) => "PUT",
+        (_, false) => "POST",
     };
 
     let inner_ret = match meta.sig.clone().output {
@@ -49,24 +64,159 @@ pub fn expand_endpoint(annot: TokenStream, item: TokenStream) -> TokenStream {
         _ => unreachable!(),
     };
 
+    let error_ty = get_error_type(&inner_ret);
     let inner_ret = err!(get_inner_type(inner_ret.clone()).map_err(|e| {
         syn::Error::new_spanned(
             ret.to_token_stream(),
-            format!("Unexpected return type (should be anyhow::Result<T>).\n{e}"),
+            format!("Unexpected return type (should be anyhow::Result<T> or Result<T, impl milrouter::ApiError>).\n{e}"),
         )
     }));
 
+    let endpoint_error = error_ty.clone().map(|e| e.to_token_stream()).unwrap_or(quote!(milrouter::anyhow::Error));
+    let client_error_impl = match &error_ty {
+        Some(e) => quote! {
+            type ClientError = milrouter::ClientError<#e>;
+
+            fn decode_error(status: milrouter::hyper::StatusCode, bytes: milrouter::bytes::Bytes) -> Self::ClientError {
+                milrouter::ClientError::from_response(status, bytes)
+            }
+        },
+        None => quote! {
+            type ClientError = milrouter::anyhow::Error;
+
+            fn decode_error(status: milrouter::hyper::StatusCode, bytes: milrouter::bytes::Bytes) -> Self::ClientError {
+                milrouter::status_error(status, &bytes)
+            }
+        },
+    };
+
     let struct_name = quote::format_ident!("Endpoint{}", AsPascalCase(name.to_string()).to_string());
     let name_str = name.to_string();
 
-    let data = args.clone().input.1;
+    // Several body arguments travel as the fields of a synthesized `Endpoint{Name}Data` struct.
+    let data_struct = format_ident!("Endpoint{}Data", AsPascalCase(name.to_string()).to_string());
+    let (input_idents, input_types): (Vec<_>, Vec<_>) = args.inputs.iter().cloned().unzip();
+    let data = match args.inputs.as_slice() {
+        [] => unit(),
+        [(_, ty)] => ty.clone(),
+        _ => syn::parse_quote!(#data_struct),
+    };
+    let data_struct_def = match args.inputs.len() {
+        0 | 1 => quote! {},
+        _ => {
+            let fields = quote! { #(pub #input_idents: #input_types),* };
+            quote! {
+                milrouter::__openapi! {
+                    #[doc(hidden)]
+                    #[derive(milrouter::serde::Serialize, milrouter::serde::Deserialize, milrouter::openapi::schemars::JsonSchema)]
+                    #[serde(crate = "milrouter::serde")]
+                    #[schemars(crate = "milrouter::openapi::schemars")]
+                    pub struct #data_struct { #fields }
+                }
+
+                milrouter::__no_openapi! {
+                    #[doc(hidden)]
+                    #[derive(milrouter::serde::Serialize, milrouter::serde::Deserialize)]
+                    #[serde(crate = "milrouter::serde")]
+                    pub struct #data_struct { #fields }
+                }
+            }
+        }
+    };
+    // How the handler turns `Data` back into the function's body arguments.
+    let (body_let, body_args) = match args.inputs.len() {
+        0 | 1 => (quote! {}, quote!(__body)),
+        _ => (quote! { let #data_struct { #(#input_idents),* } = __body; }, quote!(#(#input_idents),*)),
+    };
     let client_type = args.client.clone().map(|c| c.1).unwrap_or(unit());
     let args_tokens = args.to_tokens();
 
+    let (param_idents, param_types): (Vec<_>, Vec<_>) = args.params.iter().cloned().unzip();
+    let param_names = param_idents.iter().map(|i| i.to_string()).collect::<Vec<_>>();
+
+    // `&S` borrows from a `State<S>` held by the handler; `State<S>` is passed as-is.
+    let (state_let, state_arg) = match &args.state {
+        Some((_, ty)) => {
+            let s = state_type(ty).unwrap();
+            let arg = match ty {
+                syn::Type::Reference(_) => quote!(&*__state,),
+                _ => quote!(__state,),
+            };
+            (quote! { let __state = cx.state::<#s>().map_err(milrouter::HandlerError::Internal)?; }, arg)
+        }
+        None => (quote!(), quote!()),
+    };
+    let (extension_idents, extension_types): (Vec<_>, Vec<_>) =
+        args.extensions.iter().map(|(i, ty)| (i.clone(), extension_type(ty).unwrap())).unzip();
+    let extension_lets = quote! { #(let #extension_idents = cx.extension::<#extension_types>().map_err(milrouter::HandlerError::Internal)?;)* };
+
+    let middleware = &info.middleware;
+    let middleware_fn = match middleware.is_empty() {
+        true => quote! {},
+        false => quote! {
+            fn middleware() -> Vec<std::sync::Arc<dyn milrouter::Middleware>> {
+                vec![#( (std::sync::Arc::new(#middleware::new()) as std::sync::Arc<dyn milrouter::Middleware>) ),*]
+            }
+        },
+    };
+
+    let method_fn = match info.is_get {
+        true => quote! { fn method() -> milrouter::Method { milrouter::Method::GET } },
+        false => quote! {},
+    };
+
+    let route_fn = match info.path.clone() {
+        Some(p) => quote! { fn route() -> Option<&'static str> { Some(#p) } },
+        None => quote! {},
+    };
+
+    // The client method takes the path parameters followed by the body arguments, one parameter each.
+    // A lone unit body, such as `param1: ()`, isn't worth a parameter.
+    let client_body = match data.to_token_stream().to_string() == "()" {
+        true => &[][..],
+        false => args.inputs.as_slice(),
+    };
+    let client_args = args.params.iter().chain(client_body).cloned().collect::<Vec<_>>();
+    if client_args.len() > MAX_CLIENT_ARGS {
+        return syn::Error::new_spanned(
+            meta.sig.inputs.to_token_stream(),
+            format!("An endpoint takes at most {MAX_CLIENT_ARGS} path parameters and body arguments together"),
+        )
+        .into_compile_error()
+        .into();
+    }
+    let (client_arg_idents, client_arg_types): (Vec<_>, Vec<_>) = client_args.into_iter().unzip();
+    let (args_ty, args_pat) = match client_arg_idents.len() {
+        1 => (client_arg_types[0].to_token_stream(), client_arg_idents[0].to_token_stream()),
+        _ => (quote!((#(#client_arg_types),*)), quote!((#(#client_arg_idents),*))),
+    };
+    let data_expr = match client_body {
+        [] => quote!(()),
+        [(ident, _)] => quote!(#ident),
+        _ => quote!(#data_struct { #(#input_idents),* }),
+    };
+    let encoded_path = match info.path.clone() {
+        Some(p) => quote! { Some(milrouter::fill_route(#p, &[#((#param_names, #param_idents.to_string())),*])?) },
+        None => quote! { None },
+    };
+    let encode_args_impl = quote! {
+        impl milrouter::EndpointArgs<#client_type> for #struct_name {
+            type Args = #args_ty;
+            type Params = (#(#client_arg_types,)*);
+
+            fn encode_args(args: Self::Args) -> milrouter::anyhow::Result<(Option<String>, #data)> {
+                let #args_pat = args;
+                Ok((#encoded_path, #data_expr))
+            }
+        }
+    };
+
     let client_endpoint_impl = if is_stream {
         quote! {}
     } else if is_raw {
         quote! {
+            #encode_args_impl
+
             #[cfg(not(any(target_arch = "wasm32", target_arch = "wasm64")))]
             impl milrouter::TypedEndpoint for #struct_name {
                 type Client = #client_type;
@@ -74,6 +224,8 @@ pub fn expand_endpoint(annot: TokenStream, item: TokenStream) -> TokenStream {
 
             #[cfg(not(any(target_arch = "wasm32", target_arch = "wasm64")))]
             impl milrouter::ClientEndpoint<#client_type> for #struct_name {
+                #client_error_impl
+
                 fn decode_response(bytes: milrouter::bytes::Bytes) -> milrouter::anyhow::Result<Vec<u8>> {
                     Ok(bytes.to_vec())
                 }
@@ -81,6 +233,8 @@ pub fn expand_endpoint(annot: TokenStream, item: TokenStream) -> TokenStream {
         }
     } else {
         quote! {
+            #encode_args_impl
+
             #[cfg(not(any(target_arch = "wasm32", target_arch = "wasm64")))]
             impl milrouter::TypedEndpoint for #struct_name {
                 type Client = #client_type;
@@ -91,6 +245,8 @@ pub fn expand_endpoint(annot: TokenStream, item: TokenStream) -> TokenStream {
             where
                 #inner_ret: milrouter::serde::de::DeserializeOwned,
             {
+                #client_error_impl
+
                 fn decode_response(bytes: milrouter::bytes::Bytes) -> milrouter::anyhow::Result<#inner_ret> {
                     milrouter::serde_json::from_slice(&bytes).map_err(|e| milrouter::anyhow::anyhow!(e))
                 }
@@ -98,6 +254,45 @@ pub fn expand_endpoint(annot: TokenStream, item: TokenStream) -> TokenStream {
         }
     };
 
+    let (input_name, output_name, auth_name) = (type_name(&data), type_name(&inner_ret), auth.to_string().replace(' ', ""));
+    let mode = match (is_stream, is_raw) {
+        (true, _) => quote!(milrouter::RouteMode::Stream),
+        (_, true) => quote!(milrouter::RouteMode::Raw),
+        _ => quote!(milrouter::RouteMode::Json),
+    };
+
+    let openapi_input = match data.to_token_stream().to_string() == "()" {
+        true => quote!(None),
+        false => quote!(Some(doc.schema_for::<#data>())),
+    };
+    let openapi_output = match (is_stream, is_raw) {
+        (true, _) => quote!(milrouter::openapi::Output::Stream),
+        (_, true) => quote!(milrouter::openapi::Output::Raw),
+        _ => quote!(milrouter::openapi::Output::Json(doc.schema_for::<#inner_ret>())),
+    };
+    let openapi_error = match &error_ty {
+        Some(e) => quote!(Some(doc.schema_for::<#e>())),
+        None => quote!(None),
+    };
+    let openapi_impl = quote! {
+        milrouter::__openapi! {
+            impl milrouter::openapi::OpenApiEndpoint for #struct_name {
+                fn describe(doc: &mut milrouter::openapi::OpenApi, default_path: &str) {
+                    let operation = milrouter::openapi::Operation {
+                        id: #name_str,
+                        method: <Self as milrouter::Endpoint<#client_type>>::method(),
+                        path: <Self as milrouter::Endpoint<#client_type>>::route().unwrap_or(default_path).to_string(),
+                        params: vec![#((#param_names, doc.schema_for::<#param_types>())),*],
+                        input: #openapi_input,
+                        output: #openapi_output,
+                        error: #openapi_error,
+                    };
+                    doc.add(operation);
+                }
+            }
+        }
+    };
+
     let server_endpoint_impl = if is_stream {
         quote! {
             #[cfg(not(any(target_arch = "wasm32", target_arch = "wasm64")))]
@@ -106,12 +301,21 @@ pub fn expand_endpoint(annot: TokenStream, item: TokenStream) -> TokenStream {
                     Box::new(move |i: milrouter::hyper::HeaderMap| Box::pin(#auth(i)))
                 }
 
-                fn handler() -> milrouter::AsyncHandler3<#client_type, milrouter::hyper::HeaderMap, Self::Data, milrouter::anyhow::Result<Self::Returns>> {
+                #middleware_fn
+
+                fn handler() -> milrouter::AsyncHandler3<#client_type, milrouter::RequestContext, Self::Data, milrouter::HandlerResult<Self::Returns, Self::Error>> {
                     Box::new(move |_, _, _| Box::pin(async { unreachable!("Internal error: handler() should not be called for streaming endpoints; use stream_handler() instead.") }))
                 }
 
-                fn stream_handler() -> Option<milrouter::AsyncHandler3<#client_type, milrouter::hyper::HeaderMap, Self::Data, milrouter::anyhow::Result<milrouter::ResponseStream>>> {
-                    Some(Box::new(move |c: #client_type, h: milrouter::hyper::HeaderMap, d: Self::Data| Box::pin(#name(c, h, d))))
+                fn stream_handler() -> Option<milrouter::AsyncHandler3<#client_type, milrouter::RequestContext, Self::Data, milrouter::HandlerResult<milrouter::ResponseStream, Self::Error>>> {
+                    Some(Box::new(move |c: #client_type, cx: milrouter::RequestContext, __body: Self::Data| Box::pin(async move {
+                        #(let #param_idents = cx.params.parse::<#param_types>(#param_names)
+                            .map_err(|e| milrouter::HandlerError::Input(milrouter::InputError::path_param(#param_names, e)))?;)*
+                        #state_let
+                        #extension_lets
+                        #body_let
+                        #name(c, cx.headers, #state_arg #(#extension_idents,)* #(#param_idents,)* #body_args).await.map_err(milrouter::HandlerError::Endpoint)
+                    })))
                 }
             }
         }
@@ -124,8 +328,17 @@ pub fn expand_endpoint(annot: TokenStream, item: TokenStream) -> TokenStream {
                     Box::new(move |i: milrouter::hyper::HeaderMap| Box::pin(#auth(i)))
                 }
 
-                fn handler() -> milrouter::AsyncHandler3<#client_type, milrouter::hyper::HeaderMap, Self::Data, milrouter::anyhow::Result<Self::Returns>> {
-                    Box::new(move |i: #client_type, i2: milrouter::hyper::HeaderMap, i3: Self::Data| Box::pin(#name(i, i2, i3)))
+                #middleware_fn
+
+                fn handler() -> milrouter::AsyncHandler3<#client_type, milrouter::RequestContext, Self::Data, milrouter::HandlerResult<Self::Returns, Self::Error>> {
+                    Box::new(move |i: #client_type, cx: milrouter::RequestContext, __body: Self::Data| Box::pin(async move {
+                        #(let #param_idents = cx.params.parse::<#param_types>(#param_names)
+                            .map_err(|e| milrouter::HandlerError::Input(milrouter::InputError::path_param(#param_names, e)))?;)*
+                        #state_let
+                        #extension_lets
+                        #body_let
+                        #name(i, cx.headers, #state_arg #(#extension_idents,)* #(#param_idents,)* #body_args).await.map_err(milrouter::HandlerError::Endpoint)
+                    }))
                 }
 
                 fn is_raw() -> bool { #is_raw_val }
@@ -138,18 +351,36 @@ pub fn expand_endpoint(annot: TokenStream, item: TokenStream) -> TokenStream {
         #[derive(Clone)]
         pub struct #struct_name;
 
+        #data_struct_def
+
         impl milrouter::Endpoint<#client_type> for #struct_name {
             type Data = #data;
             type Returns = #inner_ret;
+            type Error = #endpoint_error;
 
             fn is_idempotent() -> bool { #idempotent }
             fn path() -> &'static str { #name_str }
+            #method_fn
+            #route_fn
+
+            fn descriptor(default_path: &str) -> milrouter::RouteDescriptor {
+                milrouter::RouteDescriptor {
+                    path: format!("/{}", <Self as milrouter::Endpoint<#client_type>>::route().unwrap_or(default_path).trim_matches('/')),
+                    method: <Self as milrouter::Endpoint<#client_type>>::method(),
+                    mode: #mode,
+                    input: #input_name,
+                    output: #output_name,
+                    auth: #auth_name,
+                }
+            }
         }
 
         #server_endpoint_impl
 
         #client_endpoint_impl
 
+        #openapi_impl
+
         #[cfg(not(any(target_arch = "wasm32", target_arch = "wasm64")))]
         pub async fn #name(#args_tokens) #ret #block
 
@@ -1,6 +1,9 @@
 use {
     heck::AsSnekCase,
-    crate::helpers::{parse_attrs, preamble},
+    crate::helpers::{
+        MAX_CLIENT_ARGS, client_type, group_field, parse_attrs, parse_fallback_attr, parse_group_attrs, parse_openapi_attr, preamble, variant_field,
+        variant_groups, variant_nest,
+    },
     proc_macro::{Span, TokenStream},
     quote::{ToTokens, format_ident, quote},
     syn::{parse_macro_input, DeriveInput},
@@ -10,15 +13,26 @@ pub fn expand_router(item: TokenStream) -> TokenStream {
     let (input, name, data) = preamble(parse_macro_input!(item as DeriveInput));
     let (html, local_assets, mware) = parse_attrs(input.clone());
 
+    let openapi_path = err!(parse_openapi_attr(&input));
+    let fallback = err!(parse_fallback_attr(&input));
+    let groups = err!(parse_group_attrs(&input));
     let client_name = format_ident!("{}Client", name);
+    let marker = format_ident!("__{}Marker", name);
+    let instances = format_ident!("__{}Middleware", name);
+
+    // The `#[nest]` prefix of each variant holding a router, `None` for endpoints.
+    let nests = err!(data.variants.iter().map(variant_nest).collect::<Result<Vec<_>, syn::Error>>());
 
     let first_variant = data.variants.first().map(|v| {
         let ident = &v.ident;
         let inner = v.fields.iter().next().map(|ty| ty.ty.clone());
-        quote::quote!(#name::#ident(#inner))
+        match nests[0] {
+            Some(_) => quote::quote!(#name::#ident(<#inner>::__router())),
+            None => quote::quote!(#name::#ident(#inner)),
+        }
     }).unwrap_or_else(|| quote::quote!(panic!("Router enum cannot be empty")));
 
-    let paths: Result<Vec<proc_macro2::TokenStream>, syn::Error> = data.variants.iter().map(|variant| {
+    let paths: Result<Vec<proc_macro2::TokenStream>, syn::Error> = data.variants.iter().zip(&nests).map(|(variant, nest)| {
 
         let path = format_ident!("{}", AsSnekCase(variant.ident.to_string()).to_string());
         let inner = variant.fields.iter()
@@ -30,99 +44,180 @@ pub fn expand_router(item: TokenStream) -> TokenStream {
             ))?;
 
         let inner_name = &variant.ident;
-
-        Ok(quote::quote! {
-            (stringify!(#path), i) if i == #inner::is_idempotent() => {
-                let auth = <#inner as milrouter::ServerEndpoint<_>>::auth();
-
-                let error_res = |e: String, code: u16, label: &'static str| {
-                    milrouter::tracing::info!("[-] {code} {label} /{}", stringify!(#path));
-                    milrouter::hyper::Response::builder()
-                        .status(code)
-                        .body(
-                            milrouter::Body::from(format!(
-                                "You aren't authorised to access this endpoint\n{e}"
-                            ))
-                            .boxed()
-                        )
-                        .unwrap()
+        let group_fields = variant_groups(variant, &groups)?.iter().map(group_field).collect::<Vec<_>>();
+        let field = variant_field(&variant.ident);
+
+        // A nested router gets the request with its prefix stripped, after this router's
+        // middleware and the variant's groups, and runs its own middleware inside them.
+        if let Some(prefix) = nest {
+            // Hand this router's fallback to the nested one, for when it has none of its own.
+            let inherit_fallback = fallback.as_ref().map(|fallback| quote::quote! {
+                let inherited = milrouter::InheritedFallback {
+                    uri: req.uri().clone(),
+                    prefix: req.extensions().get::<milrouter::MountPrefix>().cloned().unwrap_or_default(),
+                    handler: std::sync::Arc::new(|req| std::boxed::Box::pin(#fallback(req))),
                 };
-
-                let client = match auth(headers.clone()).await {
-                    Ok(c) => c,
-                    Err(e) => return error_res(e.to_string(), 401, "Unauthorised"),
-                };
-
-                let body: std::boxed::Box<dyn std::any::Any> = match std::any::type_name::<<#inner as milrouter::Endpoint<_>>::Data>() {
-                    "()" => std::boxed::Box::new(()),
-                    _ => {
-                        let bytes = req.collect().await.unwrap_or_else(|e| panic!("Failed to read incoming bytes for {}: {e}", stringify!(#inner_name))).to_bytes();
-                        let body_str = String::from_utf8_lossy(&bytes[..]).to_string();
-                        std::boxed::Box::new(
-                            milrouter::serde_json::from_str::<<#inner as milrouter::Endpoint<_>>::Data>(&body_str)
-                                .unwrap_or_else(|e| panic!("Failed to deserialize body for {}: {e}", stringify!(#inner_name)))
-                        )
+                req.extensions_mut().insert(inherited);
+            });
+            return Ok(quote::quote! {
+                (p, _) if p == #prefix || p.starts_with(concat!(#prefix, "/")) => 'arm: {
+                    let mut route_middleware = Vec::<std::sync::Arc<dyn milrouter::Middleware>>::new();
+                    #(route_middleware.extend(instances.#group_fields.iter().cloned());)*
+                    if let Some(response) = milrouter::run_before(&route_middleware, &req, &mut cx).await {
+                        break 'arm milrouter::run_after(&route_middleware, response, &mut cx).await;
                     }
-                };
-
-                let body: <#inner as milrouter::Endpoint<_>>::Data = *body.downcast::<<#inner as milrouter::Endpoint<_>>::Data>().unwrap();
 
-                if let Some(stream_handler) = <#inner as milrouter::ServerEndpoint<_>>::stream_handler() {
-                    return match stream_handler(client, headers, body).await {
-                        Ok(stream) => {
-                            milrouter::tracing::info!(concat!("[+] 200 Ok (stream) /", stringify!(#path)));
-                            milrouter::hyper::Response::builder()
-                                .status(200)
-                                .body(milrouter::stream_to_body(stream))
-                                .unwrap()
-                        }
-                        Err(e) => {
-                            milrouter::tracing::warn!(concat!("[-] 400 Bad Request (stream) /", stringify!(#path)));
+                    // `p` is the path without its leading `/` or `static/`, so strip those along with the prefix.
+                    let raw = req.uri().path();
+                    let strip = format!("{}{}", &raw[..raw.len() - p.len()], #prefix);
+                    let mut req = req;
+                    req.extensions_mut().extend(cx.clone());
+                    req.extensions_mut().insert(instances.#field.clone());
+                    #inherit_fallback
+                    let response = match milrouter::strip_path_prefix(&mut req, &strip) {
+                        true => match <#inner>::route(req).await {
+                            Ok(response) => response,
+                            Err(never) => match never {},
+                        },
+                        false => {
+                            milrouter::tracing::info!("[?] 404 Not Found /{}", p);
                             milrouter::hyper::Response::builder()
-                                .status(400)
-                                .body(milrouter::Body::from(e.to_string()).boxed())
+                                .status(404)
+                                .body(milrouter::Body::default().boxed())
                                 .unwrap()
                         }
                     };
+
+                    milrouter::run_after(&route_middleware, response, &mut cx).await
+                },
+            });
+        }
+
+        Ok(quote::quote! {
+            (p, m) if <#inner as milrouter::Endpoint<_>>::accepts(m) && match <#inner as milrouter::Endpoint<_>>::route() {
+                Some(route) => milrouter::match_route(route, p).is_some(),
+                None => p == stringify!(#path),
+            } => 'arm: {
+                let mut route_middleware = Vec::<std::sync::Arc<dyn milrouter::Middleware>>::new();
+                #(route_middleware.extend(instances.#group_fields.iter().cloned());)*
+                route_middleware.extend(instances.#field.iter().cloned());
+                if let Some(response) = milrouter::run_before(&route_middleware, &req, &mut cx).await {
+                    break 'arm milrouter::run_after(&route_middleware, response, &mut cx).await;
                 }
 
-                let handler = <#inner as milrouter::ServerEndpoint<_>>::handler();
+                let response = async {
+                    let auth = <#inner as milrouter::ServerEndpoint<_>>::auth();
+                    let params = <#inner as milrouter::Endpoint<_>>::route().and_then(|route| milrouter::match_route(route, p)).unwrap_or_default();
 
-                match handler(client, headers, body).await {
-                    Ok(response) => {
-                        if <#inner as milrouter::ServerEndpoint<_>>::is_raw() {
-                            use std::any::Any;
-                            let raw: std::boxed::Box<dyn Any> = std::boxed::Box::new(response);
-                            let bytes: Vec<u8> = *raw.downcast::<Vec<u8>>()
-                                .expect("Internal error: raw endpoint handler did not return Vec<u8> as expected. Ensure the endpoint function returns anyhow::Result<Vec<u8>>.");
+                    let error_res = |e: String, code: u16, label: &'static str| {
+                        milrouter::tracing::info!("[-] {code} {label} /{}", stringify!(#path));
+                        milrouter::hyper::Response::builder()
+                            .status(code)
+                            .body(
+                                milrouter::Body::from(format!(
+                                    "You aren't authorised to access this endpoint\n{e}"
+                                ))
+                                .boxed()
+                            )
+                            .unwrap()
+                    };
 
-                            milrouter::tracing::info!(concat!("[+] 200 Ok (raw) /", stringify!(#path)));
-                            milrouter::hyper::Response::builder()
-                                .status(200)
-                                .body(milrouter::Body::from(bytes.as_slice()).boxed())
-                                .unwrap()
-                        } else {
-                            let bytes = milrouter::serde_json::to_vec(&response).unwrap_or_else(|e| panic!("Failed to serialize response for {}: {e}", stringify!(#inner_name)));
+                    let client = match auth(headers.clone()).await {
+                        Ok(c) => c,
+                        Err(e) => return error_res(e.to_string(), 401, "Unauthorised"),
+                    };
 
-                            let mut compressed_file = Vec::new();
-                            milrouter::gz_compress(bytes.as_slice(), &mut compressed_file).unwrap();
+                    // Endpoints see both the request's extensions and what middleware put in its context.
+                    let mut extensions = req.extensions().clone();
+                    extensions.extend(cx.clone());
 
-                            milrouter::tracing::info!(concat!("[+] 200 Ok /", stringify!(#path)));
-                            milrouter::hyper::Response::builder()
-                                .status(200)
-                                .header("Content-Encoding", "gzip")
-                                .body(milrouter::Body::from(compressed_file.as_slice()).boxed())
-                                .unwrap()
-                        }
-                    },
-                    Err(e) => {
-                        milrouter::tracing::warn!(concat!("[-] 400 Bad Request /", stringify!(#path)));
+                    let input_error = |e: milrouter::InputError| {
+                        milrouter::tracing::info!("[-] {} Bad Input /{}: {e}", e.status, p);
+                        e.into_response()
+                    };
+                    let internal_error = |e: milrouter::anyhow::Error| {
+                        milrouter::tracing::error!("[-] 500 Internal Server Error /{}: {e}", p);
                         milrouter::hyper::Response::builder()
-                            .status(400)
-                            .body(milrouter::Body::from(e.to_string()).boxed())
+                            .status(500)
+                            .body(milrouter::Body::default().boxed())
                             .unwrap()
+                    };
+
+                    let body: std::boxed::Box<dyn std::any::Any> = match std::any::type_name::<<#inner as milrouter::Endpoint<_>>::Data>() {
+                        "()" => std::boxed::Box::new(()),
+                        _ if <#inner as milrouter::Endpoint<_>>::method() == milrouter::Method::GET => {
+                            match milrouter::read_query::<<#inner as milrouter::Endpoint<_>>::Data>(req.uri().query().unwrap_or_default()) {
+                                Ok(data) => std::boxed::Box::new(data),
+                                Err(e) => return input_error(e),
+                            }
+                        }
+                        _ => match milrouter::read_json::<<#inner as milrouter::Endpoint<_>>::Data>(req).await {
+                            Ok(data) => std::boxed::Box::new(data),
+                            Err(e) => return input_error(e),
+                        },
+                    };
+
+                    let body: <#inner as milrouter::Endpoint<_>>::Data = *body.downcast::<<#inner as milrouter::Endpoint<_>>::Data>().unwrap();
+
+                    if let Some(stream_handler) = <#inner as milrouter::ServerEndpoint<_>>::stream_handler() {
+                        return match stream_handler(client, milrouter::RequestContext { headers, params, extensions }, body).await {
+                            Ok(stream) => {
+                                milrouter::tracing::info!(concat!("[+] 200 Ok (stream) /", stringify!(#path)));
+                                milrouter::hyper::Response::builder()
+                                    .status(200)
+                                    .body(milrouter::stream_to_body(stream))
+                                    .unwrap()
+                            }
+                            Err(milrouter::HandlerError::Input(e)) => input_error(e),
+                            Err(milrouter::HandlerError::Internal(e)) => internal_error(e),
+                            Err(milrouter::HandlerError::Endpoint(e)) => {
+                                let res = milrouter::EndpointError::into_response(e);
+                                milrouter::tracing::warn!("[-] {} (stream) /{}", res.status(), stringify!(#path));
+                                res
+                            }
+                        };
                     }
-                }
+
+                    let handler = <#inner as milrouter::ServerEndpoint<_>>::handler();
+
+                    match handler(client, milrouter::RequestContext { headers, params, extensions }, body).await {
+                        Ok(response) => {
+                            if <#inner as milrouter::ServerEndpoint<_>>::is_raw() {
+                                use std::any::Any;
+                                let raw: std::boxed::Box<dyn Any> = std::boxed::Box::new(response);
+                                let bytes: Vec<u8> = *raw.downcast::<Vec<u8>>()
+                                    .expect("Internal error: raw endpoint handler did not return Vec<u8> as expected. Ensure the endpoint function returns anyhow::Result<Vec<u8>>.");
+
+                                milrouter::tracing::info!(concat!("[+] 200 Ok (raw) /", stringify!(#path)));
+                                milrouter::hyper::Response::builder()
+                                    .status(200)
+                                    .body(milrouter::Body::from(bytes.as_slice()).boxed())
+                                    .unwrap()
+                            } else {
+                                let bytes = milrouter::serde_json::to_vec(&response).unwrap_or_else(|e| panic!("Failed to serialize response for {}: {e}", stringify!(#inner_name)));
+
+                                let mut compressed_file = Vec::new();
+                                milrouter::gz_compress(bytes.as_slice(), &mut compressed_file).unwrap();
+
+                                milrouter::tracing::info!(concat!("[+] 200 Ok /", stringify!(#path)));
+                                milrouter::hyper::Response::builder()
+                                    .status(200)
+                                    .header("Content-Encoding", "gzip")
+                                    .body(milrouter::Body::from(compressed_file.as_slice()).boxed())
+                                    .unwrap()
+                            }
+                        },
+                        Err(milrouter::HandlerError::Input(e)) => input_error(e),
+                        Err(milrouter::HandlerError::Internal(e)) => internal_error(e),
+                        Err(milrouter::HandlerError::Endpoint(e)) => {
+                            let res = milrouter::EndpointError::into_response(e);
+                            milrouter::tracing::warn!("[-] {} /{}", res.status(), stringify!(#path));
+                            res
+                        }
+                    }
+                }.await;
+
+                milrouter::run_after(&route_middleware, response, &mut cx).await
             },
         })
     }).collect();
@@ -143,7 +238,7 @@ pub fn expand_router(item: TokenStream) -> TokenStream {
             Ok(quote::quote! {
                 impl milrouter::IntoRouter<#name> for #ident {
                     fn router(self) -> #name {
-                        #name::#variant(#ident)
+                        #name::#variant(self)
                     }
                 }
             })
@@ -164,60 +259,108 @@ pub fn expand_router(item: TokenStream) -> TokenStream {
         })
         .collect::<Vec<_>>();
 
-    let client_methods: Vec<proc_macro2::TokenStream> = data
+    // The client is generic over the argument types of each endpoint's method, one tuple per
+    // endpoint, and `#client_name` names it with the endpoints' `EndpointArgs::Params`.  Each method
+    // is written for every argument count, on the instantiations where that endpoint's tuple has
+    // that many fields, so exactly one of them matches and it takes the arguments one by one.
+    let client_struct = format_ident!("__{}", client_name);
+    let endpoint_variants = data
         .variants
         .iter()
-        .map(|variant| {
+        .zip(&nests)
+        .filter(|(_, nest)| nest.is_none())
+        .map(|(variant, _)| (variant, variant.fields.iter().next().map(|ty| ty.ty.clone()).unwrap()))
+        .collect::<Vec<_>>();
+    let client_params = endpoint_variants.iter().map(|(variant, _)| format_ident!("__{}", variant.ident)).collect::<Vec<_>>();
+    let client_param_types = endpoint_variants
+        .iter()
+        .map(|(_, inner)| {
+            quote::quote! { <#inner as milrouter::EndpointArgs<<#inner as milrouter::TypedEndpoint>::Client>>::Params }
+        })
+        .collect::<Vec<_>>();
+
+    let nested_client_methods: Result<Vec<proc_macro2::TokenStream>, syn::Error> = data
+        .variants
+        .iter()
+        .zip(&nests)
+        .filter_map(|(variant, nest)| nest.as_ref().map(|prefix| (variant, prefix)))
+        .map(|(variant, prefix)| {
             let inner = variant.fields.iter().next().map(|ty| ty.ty.clone()).unwrap();
             let method_name = format_ident!("{}", AsSnekCase(variant.ident.to_string()).to_string());
+            let child = client_type(&inner)?;
+            Ok(quote::quote! {
+                /// The client for the router nested under this one.
+                pub fn #method_name(&self) -> #child {
+                    <#child>::__nested(
+                        format!("{}/{}", self.host.trim_end_matches('/'), #prefix),
+                        self.headers.clone(),
+                        self.transport.clone(),
+                    )
+                }
+            })
+        })
+        .collect();
+    let nested_client_methods = err!(nested_client_methods);
 
-            quote::quote! {
-                pub async fn #method_name(
-                    &self,
-                    data: <#inner as milrouter::Endpoint<<#inner as milrouter::TypedEndpoint>::Client>>::Data,
-                ) -> milrouter::anyhow::Result<<#inner as milrouter::Endpoint<<#inner as milrouter::TypedEndpoint>::Client>>::Returns>
-                where
-                    #inner: milrouter::TypedEndpoint + milrouter::ClientEndpoint<<#inner as milrouter::TypedEndpoint>::Client>,
-                    <#inner as milrouter::Endpoint<<#inner as milrouter::TypedEndpoint>::Client>>::Data: milrouter::serde::Serialize,
-                {
-                    let url = format!("{}/{}", self.host, <#inner as milrouter::Endpoint<<#inner as milrouter::TypedEndpoint>::Client>>::path());
-                    let method = if <#inner as milrouter::Endpoint<<#inner as milrouter::TypedEndpoint>::Client>>::is_idempotent() {
-                        milrouter::reqwest::Method::PUT
-                    } else {
-                        milrouter::reqwest::Method::POST
-                    };
-                    let resp = milrouter::reqwest::Client::new()
-                        .request(method, &url)
-                        .headers(self.headers.clone())
-                        .json(&data)
-                        .send()
-                        .await?;
-
-                    let is_gzipped = resp
-                        .headers()
-                        .get("content-encoding")
-                        .and_then(|value| value.to_str().ok())
-                        .map(|value| {
-                            value
-                                .split(',')
-                                .any(|encoding| encoding.trim().eq_ignore_ascii_case("gzip"))
-                        })
-                        .unwrap_or(false);
-
-                    let bytes = resp.bytes().await?;
-                    let bytes = if is_gzipped {
-                        let mut decompressed = Vec::new();
-                        milrouter::gz_decompress(bytes.as_ref(), &mut decompressed)?;
-                        milrouter::bytes::Bytes::from(decompressed)
-                    } else {
-                        bytes
-                    };
+    let client_methods = endpoint_variants
+        .iter()
+        .enumerate()
+        .flat_map(|(i, (variant, inner))| {
+            let snake = AsSnekCase(variant.ident.to_string()).to_string();
+            let method_name = format_ident!("{}", snake);
+            let others = client_params.iter().enumerate().filter(|(j, _)| *j != i).map(|(_, p)| p.clone()).collect::<Vec<_>>();
+            let (client_params, client_struct) = (&client_params, &client_struct);
+
+            (0..=MAX_CLIENT_ARGS).map(move |count| {
+                let types = (0..count).map(|n| format_ident!("__A{n}")).collect::<Vec<_>>();
+                let values = (0..count).map(|n| format_ident!("arg{n}")).collect::<Vec<_>>();
+                let slots = client_params
+                    .iter()
+                    .enumerate()
+                    .map(|(j, p)| match j == i {
+                        true => quote::quote!((#(#types,)*)),
+                        false => quote::quote!(#p),
+                    })
+                    .collect::<Vec<_>>();
+                let (args_ty, args) = match count {
+                    1 => (quote::quote!(#(#types)*), quote::quote!(#(#values)*)),
+                    _ => (quote::quote!((#(#types),*)), quote::quote!((#(#values),*))),
+                };
+                // Without arguments the bound names no type parameter, and rustc would reject it on
+                // every other endpoint; the unused lifetime keeps it to the call site.
+                let args_bound = match count {
+                    0 => quote::quote!(for<'__args>),
+                    _ => quote::quote!(),
+                };
 
-                    <#inner as milrouter::ClientEndpoint<<#inner as milrouter::TypedEndpoint>::Client>>::decode_response(bytes)
+                quote::quote! {
+                    #[cfg(not(any(target_arch = "wasm32", target_arch = "wasm64")))]
+                    impl<#(#others,)* #(#types),*> #client_struct<#(#slots),*> {
+                        #[allow(clippy::too_many_arguments)]
+                        pub async fn #method_name(
+                            &self,
+                            #(#values: #types),*
+                        ) -> std::result::Result<
+                            <#inner as milrouter::Endpoint<<#inner as milrouter::TypedEndpoint>::Client>>::Returns,
+                            <#inner as milrouter::ClientEndpoint<<#inner as milrouter::TypedEndpoint>::Client>>::ClientError,
+                        >
+                        where
+                            #inner: milrouter::TypedEndpoint + milrouter::ClientEndpoint<<#inner as milrouter::TypedEndpoint>::Client>,
+                            #args_bound #inner: milrouter::EndpointArgs<<#inner as milrouter::TypedEndpoint>::Client, Args = #args_ty>,
+                            <#inner as milrouter::Endpoint<<#inner as milrouter::TypedEndpoint>::Client>>::Data: milrouter::serde::Serialize,
+                        {
+                            let (status, bytes) = milrouter::send_endpoint::<#inner>(&*self.transport, &self.host, &self.headers, #snake, #args).await?;
+                            if !status.is_success() {
+                                return Err(<#inner as milrouter::ClientEndpoint<<#inner as milrouter::TypedEndpoint>::Client>>::decode_error(status, bytes));
+                            }
+
+                            <#inner as milrouter::ClientEndpoint<<#inner as milrouter::TypedEndpoint>::Client>>::decode_response(bytes).map_err(Into::into)
+                        }
+                    }
                 }
-            }
+            })
         })
-        .collect();
+        .collect::<Vec<_>>();
 
     let walkdir = |p: std::path::PathBuf| {
         walkdir::WalkDir::new(&p)
@@ -261,6 +404,112 @@ pub fn expand_router(item: TokenStream) -> TokenStream {
         },
     };
 
+    // Requests no route matched: the router's `#[fallback]` handler, or the one an enclosing router
+    // handed down, or an empty 404.
+    let fallback_case = match &fallback {
+        None => quote::quote! {
+            path => match req.extensions().get::<milrouter::InheritedFallback>().cloned() {
+                Some(inherited) => {
+                    let mut req = req;
+                    req.extensions_mut().extend(cx.clone());
+                    match inherited.call(req).await {
+                        Ok(response) => {
+                            milrouter::tracing::info!("[?] {} Fallback /{}", response.status(), path.0);
+                            response
+                        }
+                        Err(err) => {
+                            milrouter::tracing::error!("[-] 500 Internal Server Error (Fallback) /{}: {err:?}", path.0);
+                            milrouter::hyper::Response::builder()
+                                .status(500)
+                                .body(milrouter::Body::from(err.to_string()).boxed())
+                                .unwrap()
+                        }
+                    }
+                }
+                None => {
+                    milrouter::tracing::info!("[?] 404 Not Found /{}", path.0);
+                    milrouter::hyper::Response::builder()
+                        .status(404)
+                        .body(milrouter::Body::default().boxed())
+                        .unwrap()
+                }
+            },
+        },
+        Some(fallback) => quote::quote! {
+            path => {
+                let mut req = req;
+                req.extensions_mut().extend(cx.clone());
+                match #fallback(req).await {
+                    Ok(response) => {
+                        milrouter::tracing::info!("[?] {} Fallback /{}", response.status(), path.0);
+                        response
+                    }
+                    Err(err) => {
+                        milrouter::tracing::error!("[-] 500 Internal Server Error (Fallback) /{}: {err:?}", path.0);
+                        milrouter::hyper::Response::builder()
+                            .status(500)
+                            .body(milrouter::Body::from(err.to_string()).boxed())
+                            .unwrap()
+                    }
+                }
+            }
+        },
+    };
+
+    let describe_endpoints = data
+        .variants
+        .iter()
+        .zip(&nests)
+        .filter_map(|(variant, nest)| {
+            let inner = variant.fields.iter().next().map(|ty| ty.ty.clone())?;
+            let snake = AsSnekCase(variant.ident.to_string()).to_string();
+            Some(match nest {
+                Some(prefix) => quote! { doc.nested(#prefix, <#inner>::__describe); },
+                None => quote! { <#inner as milrouter::openapi::OpenApiEndpoint>::describe(doc, #snake); },
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let route_descriptors = data
+        .variants
+        .iter()
+        .zip(&nests)
+        .filter_map(|(variant, nest)| {
+            let inner = variant.fields.iter().next().map(|ty| ty.ty.clone())?;
+            let snake = AsSnekCase(variant.ident.to_string()).to_string();
+            Some(match nest {
+                Some(prefix) => quote! { routes.extend(milrouter::Mount::new(#prefix, <#inner>::__router()).routes()); },
+                None => quote! { routes.push(<#inner as milrouter::Endpoint<_>>::descriptor(#snake)); },
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let openapi_serving = match openapi_path {
+        None => quote!(),
+        Some(p) => {
+            let p = p.trim_matches('/').to_string();
+            quote! {
+                milrouter::__openapi! {
+                    @required
+                    if path == #p {
+                        // Served under a prefix, the document's paths are relative to it.
+                        let mut doc = Self::openapi();
+                        if let Some(milrouter::MountPrefix(prefix)) = req.extensions().get::<milrouter::MountPrefix>().filter(|p| !p.0.is_empty()) {
+                            doc["servers"] = milrouter::serde_json::json!([{ "url": prefix }]);
+                        }
+
+                        milrouter::tracing::info!("[#] 200 Ok (OpenAPI) /{}", path);
+                        break 'route milrouter::hyper::Response::builder()
+                            .status(200)
+                            .header("Content-Type", "application/json")
+                            .body(milrouter::Body::from(milrouter::serde_json::to_vec(&doc).unwrap_or_default().as_slice()).boxed())
+                            .unwrap();
+                    }
+                }
+            }
+        }
+    };
+
     let assets_serving = match local_assets.clone() {
         Some(_local_assets) => quote::quote! {
             if let Some(file) = __ASSETS.get(&path) {
@@ -298,108 +547,73 @@ pub fn expand_router(item: TokenStream) -> TokenStream {
         }).collect()
     }).unwrap_or_default();
 
-    let mware_fn = quote! {};
-
-    let mware_impl = if mware_idents.is_empty() {
-        quote! {
-            fn middleware(&self) -> Vec<std::boxed::Box<dyn milrouter::Middleware>> {
-                vec![]
-            }
-        }
-    } else {
-        quote! {
-            fn middleware(&self) -> Vec<std::boxed::Box<dyn milrouter::Middleware>> {
-                vec![#( (Box::new(#mware_idents::new()) as Box<dyn milrouter::Middleware>) ),*]
+    // One field per group, and per variant: an endpoint's own middleware, or a nested router's instances.
+    let group_fields = groups.keys().map(|name| group_field(&format_ident!("{}", name))).collect::<Vec<_>>();
+    let group_middleware = groups.values().collect::<Vec<_>>();
+    let variant_fields = data.variants.iter().map(|variant| variant_field(&variant.ident)).collect::<Vec<_>>();
+    let (variant_types, variant_values): (Vec<_>, Vec<_>) = data
+        .variants
+        .iter()
+        .zip(&nests)
+        .map(|(variant, nest)| {
+            let inner = variant.fields.iter().next().map(|ty| ty.ty.clone()).unwrap();
+            match nest {
+                Some(_) => (
+                    quote! { std::sync::Arc<<#inner as milrouter::RouterMiddleware>::Instances> },
+                    quote! { std::sync::Arc::new(<#inner as milrouter::RouterMiddleware>::instances()) },
+                ),
+                None => (
+                    quote! { Vec<std::sync::Arc<dyn milrouter::Middleware>> },
+                    quote! { <#inner as milrouter::ServerEndpoint<_>>::middleware() },
+                ),
             }
-        }
-    };
+        })
+        .unzip();
 
-    let mware_init = if mware_idents.is_empty() {
-        quote! {}
-    } else {
-        quote! {
-            let mut middlewares = vec![#( (std::boxed::Box::new(#mware_idents::new()) as std::boxed::Box<dyn milrouter::Middleware>) ),*];
+    TokenStream::from(quote::quote! {
+        #[cfg(not(any(target_arch = "wasm32", target_arch = "wasm64")))]
+        pub struct #marker(pub ());
+
+        /// Every middleware instance the router runs, created by `new()` and `with_state()`.
+        #[cfg(not(any(target_arch = "wasm32", target_arch = "wasm64")))]
+        #[doc(hidden)]
+        pub struct #instances {
+            router: Vec<std::sync::Arc<dyn milrouter::Middleware>>,
+            #(#group_fields: Vec<std::sync::Arc<dyn milrouter::Middleware>>,)*
+            #(#variant_fields: #variant_types,)*
         }
-    };
 
-    let mware_before = if mware_idents.is_empty() {
-        quote! {}
-    } else {
-        quote! {
-            let mut __sc: Option<milrouter::hyper::Response<milrouter::http_body_util::Full<milrouter::bytes::Bytes>>> = None;
-            for mw in middlewares.iter_mut() {
-                match mw.before(&req).await {
-                    Ok(Some(response)) => { __sc = Some(response); break; }
-                    Ok(None) => {}
-                    Err(e) => {
-                        milrouter::tracing::error!("[-] 500 Middleware before hook error: {}", e);
-                        let body = milrouter::http_body_util::Full::new(milrouter::bytes::Bytes::from(e.to_string()));
-                        __sc = Some(milrouter::hyper::Response::builder()
-                            .status(500)
-                            .body(body)
-                            .unwrap());
-                        break;
-                    }
-                }
-            }
-            if let Some(sc) = __sc {
-                let status = sc.status();
-                let headers = sc.headers().clone();
-                let body = milrouter::http_body_util::BodyExt::boxed(sc.into_body());
-                let mut builder = milrouter::hyper::Response::builder().status(status);
-                for (k, v) in &headers {
-                    builder = builder.header(k, v);
-                }
-                let mut response = builder.body(body).unwrap();
-                {
-                    let (mut parts, body) = response.into_parts();
-                    let mut hollow = milrouter::hyper::Response::from_parts(parts, ());
-                for mw in middlewares.iter_mut() {
-                    if let Err(e) = mw.after(&mut hollow).await {
-                        milrouter::tracing::error!("Middleware after hook error: {}", e);
-                    }
-                }
-                    let (parts, _) = hollow.into_parts();
-                    response = milrouter::hyper::Response::from_parts(parts, body);
+        #[cfg(not(any(target_arch = "wasm32", target_arch = "wasm64")))]
+        impl milrouter::RouterMiddleware for #name {
+            type Instances = #instances;
+
+            fn instances() -> #instances {
+                #instances {
+                    router: vec![#( (std::sync::Arc::new(#mware_idents::new()) as std::sync::Arc<dyn milrouter::Middleware>) ),*],
+                    #(#group_fields: vec![#( (std::sync::Arc::new(#group_middleware::new()) as std::sync::Arc<dyn milrouter::Middleware>) ),*],)*
+                    #(#variant_fields: #variant_values,)*
                 }
-                return Ok(response);
             }
-        }
-    };
 
-    let mware_after = if mware_idents.is_empty() {
-        quote! { Ok(response) }
-    } else {
-        quote! {
-            let (mut parts, body) = response.into_parts();
-            let mut hollow = milrouter::hyper::Response::from_parts(parts, ());
-            for mw in middlewares.iter_mut() {
-                if let Err(e) = mw.after(&mut hollow).await {
-                    milrouter::tracing::error!("Middleware after hook error: {}", e);
-                }
+            fn router_middleware(instances: &#instances) -> Vec<std::sync::Arc<dyn milrouter::Middleware>> {
+                instances.router.clone()
             }
-            let (parts, _) = hollow.into_parts();
-            Ok(milrouter::hyper::Response::from_parts(parts, body))
         }
-    };
-
-    let ts = TokenStream::from(quote::quote! {
-        #mware_fn
 
         #[cfg(not(any(target_arch = "wasm32", target_arch = "wasm64")))]
-        static __ASSETS: std::sync::LazyLock<std::collections::BTreeMap::<String, (String, &'static [u8])>> = std::sync::LazyLock::new(|| {
-            use std::io::Read;
-            let mut assets = std::collections::BTreeMap::<String, (String, &'static [u8])>::new();
-            #(#inserts)*
-            assets
-        });
+        impl #name {
+            /// The router, with its middleware created once and shared by every request it serves.
+            pub fn new() -> milrouter::WithState<#name, ()> { milrouter::WithState::new(Self::__router(), ()) }
 
-        #[cfg(not(any(target_arch = "wasm32", target_arch = "wasm64")))]
-        pub struct __marker(pub ());
+            /// The bare router value, without middleware instances of its own.
+            #[doc(hidden)]
+            pub fn __router() -> #name { #first_variant }
 
-        #[cfg(not(any(target_arch = "wasm32", target_arch = "wasm64")))]
-        impl #name {
-            pub fn new() -> #name { #first_variant }
+            /// Serve this router with `state` shared by every request, for endpoints
+            /// taking a `&S` or `milrouter::State<S>` argument.
+            pub fn with_state<S: Send + Sync + 'static>(state: S) -> milrouter::WithState<#name, S> {
+                milrouter::WithState::new(Self::__router(), state)
+            }
 
             pub async fn route(req: milrouter::hyper::Request<milrouter::hyper::body::Incoming>) -> std::result::Result<milrouter::hyper::Response<milrouter::MilBody>, std::convert::Infallible> {
                 use milrouter::http_body_util::BodyExt;
@@ -409,35 +623,47 @@ pub fn expand_router(item: TokenStream) -> TokenStream {
                 let path = path.strip_prefix("static/").map(|v| v.to_string()).unwrap_or(path);
                 let headers = req.headers().clone();
                 let method = req.method().clone();
-                let is_idempotent = req.method().is_idempotent();
 
-                #mware_init
-                #mware_before
+                static __ASSETS: std::sync::LazyLock<std::collections::BTreeMap::<String, (String, &'static [u8])>> = std::sync::LazyLock::new(|| {
+                    let mut assets = std::collections::BTreeMap::<String, (String, &'static [u8])>::new();
+                    #(#inserts)*
+                    assets
+                });
+
+                // Per-request context shared by every middleware hook, router-wide and scoped.
+                let mut cx = milrouter::hyper::http::Extensions::new();
+                // The instances of the `WithState` serving this router, or of the parent nesting it.
+                // A bare router value has none, and gets new ones for this request.
+                let instances = req
+                    .extensions()
+                    .get::<std::sync::Arc<#instances>>()
+                    .cloned()
+                    .unwrap_or_else(|| std::sync::Arc::new(<#name as milrouter::RouterMiddleware>::instances()));
+                let middleware = instances.router.clone();
+                if let Some(response) = milrouter::run_before(&middleware, &req, &mut cx).await {
+                    return Ok(milrouter::run_after(&middleware, response, &mut cx).await);
+                }
 
                 let response: milrouter::hyper::Response<milrouter::MilBody> = 'route: {
                     if method == milrouter::hyper::Method::GET {
+                        #openapi_serving
                         #assets_serving
                         #default_route_case
-                        break 'route milrouter::hyper::Response::builder()
-                            .status(404)
-                            .body(milrouter::Body::default().boxed())
-                            .unwrap();
                     }
 
                     break 'route match milrouter::tokio::task::spawn(async move {
-                        match (path.as_str(), is_idempotent) {
+                        let response = match (path.as_str(), &method) {
                             #(#paths)*
-                            path => {
-                                milrouter::tracing::info!("[?] 404 Not Found /{}", path.0);
-                                milrouter::hyper::Response::builder()
-                                    .status(404)
-                                    .body(milrouter::Body::default().boxed())
-                                    .unwrap()
-                            }
-                        }
+                            #fallback_case
+                        };
+                        (response, cx)
                     }).await {
-                        Ok(inner) => inner,
+                        Ok((inner, returned)) => {
+                            cx = returned;
+                            inner
+                        }
                         Err(err) => {
+                            cx = milrouter::hyper::http::Extensions::new();
                             let err = err.into_panic();
                             let value = err
                                 .downcast_ref::<String>()
@@ -454,26 +680,75 @@ pub fn expand_router(item: TokenStream) -> TokenStream {
                     };
                 };
 
-                #mware_after
+                Ok(milrouter::run_after(&middleware, response, &mut cx).await)
+            }
+
+            milrouter::__openapi! {
+                /// The OpenAPI 3.1 document describing every endpoint of this router.
+                pub fn openapi() -> milrouter::serde_json::Value {
+                    let mut doc = milrouter::openapi::OpenApi::new(stringify!(#name), env!("CARGO_PKG_VERSION"));
+                    Self::__describe(&mut doc);
+                    doc.into_json()
+                }
+
+                /// Add this router's endpoints, and those of its nested routers, to `doc`.
+                #[doc(hidden)]
+                pub fn __describe(doc: &mut milrouter::openapi::OpenApi) {
+                    #(#describe_endpoints)*
+                }
             }
 
             #[cfg(not(any(target_arch = "wasm32", target_arch = "wasm64")))]
             pub fn client(host: String, headers: milrouter::hyper::HeaderMap) -> #client_name {
-                #client_name { host, headers }
+                Self::client_with(host, headers, milrouter::reqwest::Client::new())
+            }
+
+            /// Like `client`, but sends requests through `http`, e.g. one trusting a custom root certificate.
+            #[cfg(not(any(target_arch = "wasm32", target_arch = "wasm64")))]
+            pub fn client_with(host: String, headers: milrouter::hyper::HeaderMap, http: milrouter::reqwest::Client) -> #client_name {
+                Self::client_with_transport(host, headers, http)
+            }
+
+            /// Like `client`, but sends requests through any `milrouter::ClientTransport`,
+            /// such as an in-process `milrouter::testing::TestServer`.
+            #[cfg(not(any(target_arch = "wasm32", target_arch = "wasm64")))]
+            pub fn client_with_transport(host: String, headers: milrouter::hyper::HeaderMap, transport: impl milrouter::ClientTransport) -> #client_name {
+                #client_name::__nested(host, headers, std::sync::Arc::new(transport))
+            }
+
+            /// Build a client that talks to a router served with `milrouter::serve_unix` at `path`.
+            #[cfg(all(unix, not(any(target_arch = "wasm32", target_arch = "wasm64"))))]
+            pub fn client_unix(path: impl AsRef<std::path::Path>, headers: milrouter::hyper::HeaderMap) -> milrouter::anyhow::Result<#client_name> {
+                let http = milrouter::reqwest::Client::builder().unix_socket(path.as_ref()).build()?;
+                Ok(Self::client_with("http://localhost".to_string(), headers, http))
             }
         }
 
+        /// The typed client, used through its alias without the leading underscores.
         #[cfg(not(any(target_arch = "wasm32", target_arch = "wasm64")))]
-        pub struct #client_name {
+        pub struct #client_struct<#(#client_params),*> {
             host: String,
             headers: milrouter::hyper::HeaderMap,
+            transport: std::sync::Arc<dyn milrouter::ClientTransport>,
+            params: std::marker::PhantomData<fn() -> (#(#client_params,)*)>,
         }
 
         #[cfg(not(any(target_arch = "wasm32", target_arch = "wasm64")))]
-        impl #client_name {
-            #(#client_methods)*
+        pub type #client_name = #client_struct<#(#client_param_types),*>;
+
+        #[cfg(not(any(target_arch = "wasm32", target_arch = "wasm64")))]
+        impl<#(#client_params),*> #client_struct<#(#client_params),*> {
+            /// Used by `client_with_transport`, and by a parent router's client for the routers it nests.
+            #[doc(hidden)]
+            pub fn __nested(host: String, headers: milrouter::hyper::HeaderMap, transport: std::sync::Arc<dyn milrouter::ClientTransport>) -> Self {
+                #client_struct { host, headers, transport, params: std::marker::PhantomData }
+            }
+
+            #(#nested_client_methods)*
         }
 
+        #(#client_methods)*
+
         impl std::fmt::Display for #name {
             fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
                 match self {
@@ -487,10 +762,18 @@ pub fn expand_router(item: TokenStream) -> TokenStream {
                 Box::pin(#name::route(req))
             }
 
-            #mware_impl
+            fn middleware(&self) -> Vec<std::sync::Arc<dyn milrouter::Middleware>> {
+                <#name as milrouter::RouterMiddleware>::instances().router
+            }
+
+            fn routes(&self) -> Vec<milrouter::RouteDescriptor> {
+                let mut routes = Vec::new();
+                #(#route_descriptors)*
+                routes
+            }
         }
 
-        impl milrouter::Router for __marker {
+        impl milrouter::Router for #marker {
             fn route(&self, _: milrouter::hyper::Request<milrouter::hyper::body::Incoming>) -> std::pin::Pin<std::boxed::Box<dyn std::future::Future<Output = std::result::Result<milrouter::hyper::Response<milrouter::MilBody>, std::convert::Infallible>> + std::marker::Send + 'static>> {
                 Box::pin(async move {
                     milrouter::tracing::warn!("[*] 418 I'm a teapot");
@@ -501,20 +784,22 @@ pub fn expand_router(item: TokenStream) -> TokenStream {
                 })
             }
 
-            fn middleware(&self) -> Vec<std::boxed::Box<dyn milrouter::Middleware>> {
+            fn middleware(&self) -> Vec<std::sync::Arc<dyn milrouter::Middleware>> {
+                vec![]
+            }
+
+            fn routes(&self) -> Vec<milrouter::RouteDescriptor> {
                 vec![]
             }
         }
 
-        impl std::fmt::Display for __marker {
+        impl std::fmt::Display for #marker {
             fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-                write!(f, "__marker")
+                f.write_str(stringify!(#marker))
             }
         }
 
         #(#into_routers)*
 
-    });
-
-    ts
+    })
 }
\ No newline at end of file